@@ -0,0 +1,62 @@
+use crate::config::CONFIG_PATH_ENV;
+
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+pub const USAGE: &str = "\
+Usage: olarm_mqtt_bridge [--config <path>] [<command> [--json]]
+
+Without a command the bridge runs. Commands:
+  init-config                      write an example config
+  check-config [--connect]         validate the config, optionally logging in and connecting
+  purge-discovery <device_key>     remove a device's Home Assistant discovery configs
+  devices                          list the devices of every account
+  status <device>                  print area and zone states
+  arm|stay|sleep|disarm <device> <area>
+  bypass|unbypass <device> <zone>
+  actions <device>                 list recent actions
+  events <device>                  list recent events
+
+<device> is a device id, IMEI, serial, name or alias. --json prints the API response instead.";
+
+/// Command line: `[--config <path>] [<command> [<args>...]]`
+#[derive(Debug, Clone)]
+pub struct Cli {
+    /// From `--config`, else the `OLARM_BRIDGE_CONFIG` env var, else `config.toml`
+    pub config_path: String,
+    /// Without a command the bridge runs
+    pub command: Option<String>,
+    pub args: Vec<String>,
+}
+
+impl Cli {
+    pub fn parse() -> anyhow::Result<Self> {
+        Self::from_args(std::env::args().skip(1))
+    }
+
+    fn from_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut config_path = None;
+        let mut positional = Vec::new();
+        while let Some(arg) = args.next() {
+            if arg == "--config" || arg == "-c" {
+                config_path = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow::anyhow!("{} requires a path", arg))?,
+                );
+            } else if let Some(path) = arg.strip_prefix("--config=") {
+                config_path = Some(path.to_string());
+            } else {
+                positional.push(arg);
+            }
+        }
+        let config_path = config_path
+            .or_else(|| std::env::var(CONFIG_PATH_ENV).ok())
+            .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+
+        let mut positional = positional.into_iter();
+        Ok(Self {
+            config_path,
+            command: positional.next(),
+            args: positional.collect(),
+        })
+    }
+}
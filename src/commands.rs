@@ -0,0 +1,296 @@
+use crate::cli::{Cli, USAGE};
+use crate::config::Config;
+use crate::olarm_api::models::request::actions_request::{ActionCmd, ActionsRequest};
+use crate::olarm_api::models::response::user_response::UserDevice;
+use crate::olarm_api::models::zone_status::ZoneStatus;
+use crate::olarm_api::olarm_client::{OlarmApiTrait, OlarmClient};
+use chrono::DateTime;
+use serde::Serialize;
+
+/// Number of arguments each one-shot command takes. These run once against the Olarm API and
+/// exit, without connecting to Home Assistant.
+fn arity(command: &str) -> Option<usize> {
+    match command {
+        "devices" => Some(0),
+        "status" | "actions" | "events" => Some(1),
+        "arm" | "stay" | "sleep" | "disarm" | "bypass" | "unbypass" => Some(2),
+        _ => None,
+    }
+}
+
+pub fn is_command(command: &str) -> bool {
+    arity(command).is_some()
+}
+
+/// A device of one of the configured accounts, with the client to reach it
+struct AccountDevice {
+    account: String,
+    client: OlarmClient,
+    device: UserDevice,
+}
+
+/// A row of `devices --json`. Leaves out the rest of the user device, such as the master code.
+#[derive(Serialize)]
+struct DeviceListing<'a> {
+    account: &'a str,
+    id: &'a str,
+    imei: &'a str,
+    serial: &'a str,
+    name: &'a str,
+    alias: Option<&'a str>,
+    status: &'a str,
+    bridged: bool,
+}
+
+pub async fn run(cli: &Cli) -> anyhow::Result<()> {
+    let config = Config::load(&cli.config_path)?;
+    let json = cli.args.iter().any(|arg| arg == "--json");
+    let args = cli
+        .args
+        .iter()
+        .filter(|arg| *arg != "--json")
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    let command = cli.command.as_deref().unwrap_or_default();
+    if arity(command) != Some(args.len()) {
+        anyhow::bail!("Invalid arguments for {}\n\n{}", command, USAGE);
+    }
+    let devices = account_devices(&config).await?;
+
+    match (command, args.as_slice()) {
+        ("devices", []) => list_devices(&config, &devices, json),
+        ("status", [device]) => status(resolve(&config, &devices, device)?, json).await,
+        ("arm", [device, area]) => area_action(&config, &devices, device, area, ActionCmd::AreaArm).await,
+        ("stay", [device, area]) => area_action(&config, &devices, device, area, ActionCmd::AreaStay).await,
+        ("sleep", [device, area]) => area_action(&config, &devices, device, area, ActionCmd::AreaSleep).await,
+        ("disarm", [device, area]) => {
+            area_action(&config, &devices, device, area, ActionCmd::AreaDisarm).await
+        }
+        ("bypass", [device, zone]) => zone_action(&config, &devices, device, zone, ActionCmd::ZoneBypass).await,
+        ("unbypass", [device, zone]) => {
+            zone_action(&config, &devices, device, zone, ActionCmd::ZoneUnBypass).await
+        }
+        ("actions", [device]) => actions(resolve(&config, &devices, device)?, json).await,
+        ("events", [device]) => events(resolve(&config, &devices, device)?, json).await,
+        _ => unreachable!("arity checked above"),
+    }
+}
+
+/// Logs into every configured account and lists its devices, filtered or not
+async fn account_devices(config: &Config) -> anyhow::Result<Vec<AccountDevice>> {
+    let mut devices = Vec::new();
+    for account in config.olarm.accounts() {
+        let client = OlarmClient::new(account.api_token.clone(), &account.username, &account.password);
+        let login_response = client.get_oauth_response().await?;
+        let user = client.get_user(&login_response.user_index.to_string()).await?;
+        for device in user.devices {
+            devices.push(AccountDevice {
+                account: account.name.clone(),
+                client: client.clone(),
+                device,
+            });
+        }
+    }
+    Ok(devices)
+}
+
+/// Finds a device by id, IMEI, serial, name or alias, ignoring case
+fn resolve<'a>(config: &Config, devices: &'a [AccountDevice], query: &str) -> anyhow::Result<&'a AccountDevice> {
+    let aliases = config.device_aliases();
+    let matches = devices
+        .iter()
+        .filter(|entry| {
+            let device = &entry.device;
+            [Some(&device.id), Some(&device.imei), Some(&device.serial), Some(&device.name), aliases.get(&device.id)]
+                .into_iter()
+                .flatten()
+                .any(|value| value.eq_ignore_ascii_case(query))
+        })
+        .collect::<Vec<_>>();
+    match matches.as_slice() {
+        [] => anyhow::bail!("No device matches {:?}, run `devices` to list them", query),
+        [entry] => Ok(entry),
+        _ => anyhow::bail!(
+            "{:?} matches several devices, use the device id instead: {}",
+            query,
+            matches
+                .iter()
+                .map(|entry| format!("{} ({})", entry.device.name, entry.device.id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+fn account_label(account: &str) -> &str {
+    if account.is_empty() { "default" } else { account }
+}
+
+fn format_millis(millis: i64) -> String {
+    DateTime::from_timestamp_millis(millis)
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn list_devices(config: &Config, devices: &[AccountDevice], json: bool) -> anyhow::Result<()> {
+    let aliases = config.device_aliases();
+    let listings = devices
+        .iter()
+        .map(|entry| DeviceListing {
+            account: account_label(&entry.account),
+            id: &entry.device.id,
+            imei: &entry.device.imei,
+            serial: &entry.device.serial,
+            name: &entry.device.name,
+            alias: aliases.get(&entry.device.id).map(String::as_str),
+            status: &entry.device.status,
+            bridged: config.bridges_device(&entry.account, &entry.device),
+        })
+        .collect::<Vec<_>>();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&listings)?);
+        return Ok(());
+    }
+    for listing in listings {
+        println!(
+            "{}\t{}\tIMEI {}\tserial {}\t{}{}\t{}{}",
+            listing.account,
+            listing.id,
+            listing.imei,
+            listing.serial,
+            listing.name,
+            listing.alias.map(|alias| format!(" ({})", alias)).unwrap_or_default(),
+            listing.status,
+            if listing.bridged { "" } else { "\tnot bridged" }
+        );
+    }
+    Ok(())
+}
+
+async fn status(entry: &AccountDevice, json: bool) -> anyhow::Result<()> {
+    let device = entry.client.get_device(&entry.device.id).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&device.device_state)?);
+        return Ok(());
+    }
+    let state = &device.device_state;
+    let profile = &device.device_profile;
+    println!("{} ({}): {}", device.device_name, device.device_id, device.device_status);
+    for (index, area_state) in state.areas.iter().enumerate() {
+        println!(
+            "Area {} {}: {}, since {}",
+            index + 1,
+            profile.areas_labels.get(index).map(String::as_str).unwrap_or_default(),
+            area_state,
+            state
+                .areas_stamp
+                .get(index)
+                .map(|stamp| format_millis(*stamp as i64))
+                .unwrap_or_else(|| "-".to_string())
+        );
+    }
+    let zone_count = (profile.zones_limit.max(0) as usize).min(state.zones.len());
+    for (index, zone_state) in state.zones.iter().enumerate().take(zone_count) {
+        println!(
+            "Zone {} {}: {}, since {}",
+            index + 1,
+            profile.zones_labels.get(index).map(String::as_str).unwrap_or_default(),
+            ZoneStatus::from(zone_state.as_str()).code(),
+            state
+                .zones_stamp
+                .get(index)
+                .copied()
+                .flatten()
+                .map(|stamp| format_millis(stamp as i64))
+                .unwrap_or_else(|| "-".to_string())
+        );
+    }
+    Ok(())
+}
+
+async fn send_action(entry: &AccountDevice, action_cmd: ActionCmd, action_num: usize) -> anyhow::Result<()> {
+    let _ = entry
+        .client
+        .send_action(
+            &entry.device.id,
+            ActionsRequest {
+                action_cmd,
+                action_num: action_num.to_string(),
+            },
+        )
+        .await?
+        .error_for_status()?;
+    println!("Sent {} {} to {} ({})", action_cmd, action_num, entry.device.name, entry.device.id);
+    Ok(())
+}
+
+fn parse_number(kind: &str, value: &str, limit: i64) -> anyhow::Result<usize> {
+    match value.parse::<usize>() {
+        Ok(number) if number >= 1 && number as i64 <= limit => Ok(number),
+        _ => anyhow::bail!("{} must be a number from 1 to {}, got {:?}", kind, limit, value),
+    }
+}
+
+async fn area_action(
+    config: &Config,
+    devices: &[AccountDevice],
+    device: &str,
+    area: &str,
+    action_cmd: ActionCmd,
+) -> anyhow::Result<()> {
+    let entry = resolve(config, devices, device)?;
+    let area_number = parse_number("Area", area, entry.device.profile.areas_limit)?;
+    send_action(entry, action_cmd, area_number).await
+}
+
+async fn zone_action(
+    config: &Config,
+    devices: &[AccountDevice],
+    device: &str,
+    zone: &str,
+    action_cmd: ActionCmd,
+) -> anyhow::Result<()> {
+    let entry = resolve(config, devices, device)?;
+    let zone_number = parse_number("Zone", zone, entry.device.profile.zones_limit)?;
+    send_action(entry, action_cmd, zone_number).await
+}
+
+async fn actions(entry: &AccountDevice, json: bool) -> anyhow::Result<()> {
+    let response = entry.client.get_actions(&entry.device.id).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&response.actions)?);
+        return Ok(());
+    }
+    for action in response.actions {
+        println!(
+            "{}\t{} {}\t{}\t{}\t{}",
+            format_millis(action.action_created),
+            action.action_cmd,
+            action.action_num,
+            action.action_status,
+            action.user_fullname,
+            action.action_msg
+        );
+    }
+    Ok(())
+}
+
+async fn events(entry: &AccountDevice, json: bool) -> anyhow::Result<()> {
+    let response = entry.client.get_events(&entry.device.id).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&response.data)?);
+        return Ok(());
+    }
+    for event in response.data {
+        println!(
+            "{}\t{} {}\t{}\t{}\t{}",
+            format_millis(event.event_time as i64),
+            event.event_action,
+            event.event_num,
+            event.event_state,
+            event.user_fullname,
+            event.event_msg
+        );
+    }
+    Ok(())
+}
@@ -1,17 +1,38 @@
 ﻿use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use crate::olarm_api::models::response::user_response::UserDevice;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+/// Env var naming the config file when `--config` isn't given
+pub const CONFIG_PATH_ENV: &str = "OLARM_BRIDGE_CONFIG";
+
+/// `OLARM_BRIDGE__<SECTION>__<KEY>` env vars override config values
+const ENV_OVERRIDE_PREFIX: &str = "OLARM_BRIDGE__";
+
+/// Keys that may instead be read from a file named by `<key>_file`, e.g. a Docker secret
+const SECRET_KEYS: [&str; 3] = ["api_token", "password", "mqtt_password"];
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct Config {
     pub logging: LoggingConfig,
     pub olarm: OlarmConfig,
     pub home_assistant: HomeAssistantConfig,
     pub intervals: IntervalConfig,
     pub limits: LimitsConfig,
+    #[serde(default)]
+    pub topics: TopicsConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    /// Overrides of how Olarm zone type codes are shown in HA, keyed by type code
+    #[serde(default)]
+    pub zone_types: HashMap<String, ZoneTypeConfig>,
+    /// Per-device settings, keyed by Olarm device id
+    #[serde(default)]
+    pub devices: HashMap<String, DeviceConfig>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct LoggingConfig {
     pub directory: String,
     pub debug_file: String,
@@ -21,47 +42,662 @@ pub struct LoggingConfig {
     pub console_level: String,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct OlarmConfig {
+    /// Credentials of the single account used when no `[[olarm.accounts]]` are configured
+    #[serde(default)]
     pub api_token: String,
+    #[serde(default)]
     pub username: String,
+    #[serde(default)]
     pub password: String,
     pub broker_url: String,
     pub broker_port: u16,
+    /// Devices bridged from every account, on top of each account's own filter
+    #[serde(default)]
+    pub devices: DeviceFilter,
+    /// Olarm accounts to bridge. Their devices' topics and unique ids are namespaced by account name.
+    #[serde(default)]
+    pub accounts: Vec<OlarmAccountConfig>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct OlarmAccountConfig {
+    /// Namespace of the account's topics and unique ids. Empty only for the single legacy account.
+    pub name: String,
+    pub api_token: String,
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub devices: DeviceFilter,
+}
+
+/// Which devices are bridged. Each pattern is matched against the device's id, IMEI, serial
+/// and name, ignoring case, and may use `*` and `?` wildcards (e.g. `"*Monitoring*"`).
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct DeviceFilter {
+    /// Only bridge matching devices. When empty, every device is bridged.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Never bridge matching devices, even if included
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl DeviceFilter {
+    pub fn allows(&self, device: &UserDevice) -> bool {
+        self.allows_identifiers(&[&device.id, &device.imei, &device.serial, &device.name])
+    }
+
+    fn allows_identifiers(&self, identifiers: &[&str]) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|pattern| Self::matches(pattern, identifiers)))
+            && !self.exclude.iter().any(|pattern| Self::matches(pattern, identifiers))
+    }
+
+    fn matches(pattern: &str, identifiers: &[&str]) -> bool {
+        identifiers
+            .iter()
+            .any(|value| glob_match(&pattern.to_lowercase(), &value.to_lowercase()))
+    }
+}
+
+/// Whether `value` matches `pattern`, where `*` matches any run of characters and `?` any one
+fn glob_match(pattern: &str, value: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let value = value.chars().collect::<Vec<_>>();
+    let (mut p, mut v) = (0, 0);
+    // Position of the last `*` and of the value character it currently ends on
+    let mut backtrack: Option<(usize, usize)> = None;
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if let Some((star, star_v)) = backtrack {
+            // Let the last `*` swallow one more character
+            backtrack = Some((star, star_v + 1));
+            p = star + 1;
+            v = star_v + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+impl OlarmConfig {
+    /// The configured accounts, or the legacy top-level credentials as one un-namespaced account
+    pub fn accounts(&self) -> Vec<OlarmAccountConfig> {
+        if self.accounts.is_empty() {
+            return vec![OlarmAccountConfig {
+                name: String::new(),
+                api_token: self.api_token.clone(),
+                username: self.username.clone(),
+                password: self.password.clone(),
+                devices: DeviceFilter::default(),
+            }];
+        }
+        self.accounts.clone()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct HomeAssistantConfig {
     pub mqtt_host: String,
     pub mqtt_port: u16,
     pub mqtt_username: String,
     pub mqtt_password: String,
     pub client_id: String,
+    #[serde(default)]
+    pub transport: BrokerTransport,
+    /// Path of the websocket endpoint, for the `ws` and `wss` transports
+    #[serde(default = "default_ws_path")]
+    pub ws_path: String,
+    /// PEM bundle of the CAs the broker's certificate is checked against, instead of the system's
+    #[serde(default)]
+    pub ca_file: Option<String>,
+    /// PEM client certificate and private key, for brokers that require client authentication
+    #[serde(default)]
+    pub client_cert_file: Option<String>,
+    #[serde(default)]
+    pub client_key_file: Option<String>,
+    /// ALPN protocols offered during the TLS handshake, e.g. `["mqtt"]`
+    #[serde(default)]
+    pub alpn: Vec<String>,
+    /// 0 disables keep-alive pings
+    #[serde(default = "default_ha_keep_alive_seconds")]
+    pub keep_alive_seconds: u64,
+    /// Whether the broker drops the bridge's session and subscriptions when it disconnects
+    #[serde(default = "default_clean_session")]
+    pub clean_session: bool,
+}
+
+fn default_ws_path() -> String {
+    "/mqtt".to_string()
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+fn default_ha_keep_alive_seconds() -> u64 {
+    60
+}
+
+fn default_clean_session() -> bool {
+    true
+}
+
+impl HomeAssistantConfig {
+    /// Whether certificates and ALPN apply, which they only do over TLS
+    pub fn uses_tls(&self) -> bool {
+        matches!(self.transport, BrokerTransport::Tls | BrokerTransport::Wss)
+    }
+}
+
+/// How the bridge connects to the Home Assistant broker
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BrokerTransport {
+    /// Plain MQTT over TCP
+    #[default]
+    Tcp,
+    /// MQTT over TLS
+    Tls,
+    /// MQTT over a websocket
+    Ws,
+    /// MQTT over a websocket secured with TLS
+    Wss,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct IntervalConfig {
     pub status_tick_seconds: u64,
     pub reconnect_delay_seconds: u64,
     pub mqtt_keep_alive_seconds: u64,
+    /// How often zone/area labels and limits are re-read from the Olarm API
+    #[serde(default = "default_profile_refresh_seconds")]
+    pub profile_refresh_seconds: u64,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+fn default_profile_refresh_seconds() -> u64 {
+    300
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct LimitsConfig {
     pub mqtt_queue_size: usize,
     pub command_channel_size: usize,
     pub max_concurrent_commands: usize,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct TopicsConfig {
+    /// Home Assistant's MQTT discovery prefix
+    pub discovery_prefix: String,
+    /// Prefix of every state, attribute and command topic the bridge uses
+    pub base_topic: String,
+    pub names: NameTemplates,
+}
+
+impl Default for TopicsConfig {
+    fn default() -> Self {
+        Self {
+            discovery_prefix: "homeassistant".to_string(),
+            base_topic: "olarm".to_string(),
+            names: NameTemplates::default(),
+        }
+    }
+}
+
+/// Where the bridge keeps state that must survive restarts
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub directory: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            directory: "./data".to_string(),
+        }
+    }
+}
+
+impl StorageConfig {
+    pub fn discovery_registry_path(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.directory).join("discovery.json")
+    }
+
+    pub fn trip_counters_path(&self, device_id: &str) -> std::path::PathBuf {
+        std::path::Path::new(&self.directory).join(format!("zone_trips_{}.json", device_id))
+    }
+}
+
+/// Entity name templates. Placeholders: `{device}`, `{area}` (number), `{area_label}`,
+/// `{zone}` (label), `{zone_number}`, `{output}` (on-board PGM or door label) and `{entity}`
+/// (e.g. "Fire", "Countdown").
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct NameTemplates {
+    pub device: String,
+    pub area: String,
+    pub area_entity: String,
+    pub zone: String,
+    pub zone_bypass: String,
+    pub zone_entity: String,
+    pub output: String,
+}
+
+impl Default for NameTemplates {
+    fn default() -> Self {
+        Self {
+            device: "Olarm {device}".to_string(),
+            area: "Olarm {device} Area {area}".to_string(),
+            area_entity: "Olarm {device} Area {area} {entity}".to_string(),
+            zone: "{zone}".to_string(),
+            zone_bypass: "{zone} Bypass ({device})".to_string(),
+            zone_entity: "{zone} {entity}".to_string(),
+            output: "{output} {entity} ({device})".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct DeviceConfig {
+    /// Replaces the Olarm device name in entity names and the device id in topics
+    #[serde(default)]
+    pub alias: Option<String>,
+    #[serde(default)]
+    pub command_transport: CommandTransport,
+    /// Zone numbers belonging to each area, starting with area 1.
+    /// When empty, every zone is treated as part of every area.
+    #[serde(default)]
+    pub area_zones: Vec<Vec<usize>>,
+    /// Refuse arm commands while Olarm reports the area as `notready`
+    #[serde(default)]
+    pub reject_arm_when_not_ready: bool,
+    /// Exit delay of each area in seconds, starting with area 1
+    #[serde(default)]
+    pub exit_delay_seconds: Vec<u64>,
+    /// Entry delay of each area in seconds, starting with area 1
+    #[serde(default)]
+    pub entry_delay_seconds: Vec<u64>,
+    /// Per-zone overrides, keyed by zone number
+    #[serde(default)]
+    pub zones: HashMap<String, ZoneOverride>,
+}
+
+/// How zones of an Olarm zone type are shown in HA
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct ZoneTypeConfig {
+    #[serde(default)]
+    pub device_class: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Seconds after which HA resets the zone to off
+    #[serde(default)]
+    pub off_delay: Option<u64>,
+    /// Minimum seconds the bridge keeps reporting a zone as on before reporting it off
+    #[serde(default)]
+    pub min_on_seconds: Option<u64>,
+    /// Minimum seconds the bridge keeps reporting a zone as off before reporting it on
+    #[serde(default)]
+    pub min_off_seconds: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct ZoneOverride {
+    #[serde(default)]
+    pub device_class: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Don't create HA entities for the zone
+    #[serde(default)]
+    pub hide: bool,
+    /// Seconds after which HA resets the zone to off
+    #[serde(default)]
+    pub off_delay: Option<u64>,
+    #[serde(default)]
+    pub min_on_seconds: Option<u64>,
+    #[serde(default)]
+    pub min_off_seconds: Option<u64>,
+}
+
+impl DeviceConfig {
+    /// First configured area containing the zone
+    pub fn zone_area(&self, zone_number: usize) -> Option<usize> {
+        self.area_zones
+            .iter()
+            .position(|zones| zones.contains(&zone_number))
+            .map(|index| index + 1)
+    }
+
+    pub fn zone_in_area(&self, zone_number: usize, area_number: usize) -> bool {
+        if self.area_zones.is_empty() {
+            return true;
+        }
+        self.area_zones
+            .get(area_number.wrapping_sub(1))
+            .is_some_and(|zones| zones.contains(&zone_number))
+    }
+
+    pub fn exit_delay(&self, area_number: usize) -> Option<u64> {
+        self.exit_delay_seconds.get(area_number.wrapping_sub(1)).copied()
+    }
+
+    pub fn entry_delay(&self, area_number: usize) -> Option<u64> {
+        self.entry_delay_seconds.get(area_number.wrapping_sub(1)).copied()
+    }
+
+    pub fn zone_override(&self, zone_number: usize) -> Option<&ZoneOverride> {
+        self.zones.get(&zone_number.to_string())
+    }
+}
+
+/// How commands from Home Assistant are delivered to the Olarm device. Commands without a known
+/// MQTT name, such as unbypass, PGM and ukey commands, always go over REST.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CommandTransport {
+    /// Publish on the device's `si/app/v2/<imei>/control` MQTT topic
+    Mqtt,
+    /// POST to the Olarm REST actions endpoint
+    Rest,
+    /// Use MQTT while the device's Olarm MQTT connection is up, and REST while it is down or if a
+    /// publish fails. A command that was published but not answered isn't resent, as it may still
+    /// be applied.
+    #[default]
+    MqttWithRestFallback,
+}
+
 impl Config {
-    pub fn from_file(path: &str) -> Result<Self> {
-        let content = fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
+    /// Settings for a specific device, falling back to defaults if it has no section
+    pub fn device_config(&self, device_id: &str) -> DeviceConfig {
+        self.devices.get(device_id).cloned().unwrap_or_default()
+    }
+
+    /// Configured device aliases, keyed by device id
+    pub fn device_aliases(&self) -> HashMap<String, String> {
+        self.devices
+            .iter()
+            .filter_map(|(device_id, device)| Some((device_id.clone(), device.alias.clone()?)))
+            .collect()
+    }
+
+    /// Whether `device` of the named account is bridged
+    pub fn bridges_device(&self, account_name: &str, device: &UserDevice) -> bool {
+        self.olarm.devices.allows(device)
+            && self
+                .olarm
+                .accounts()
+                .iter()
+                .find(|account| account.name == account_name)
+                .is_some_and(|account| account.devices.allows(device))
+    }
+
+    /// Whether moving to `new` changes anything a running device connection was set up with
+    pub fn affects_device(&self, new: &Config, device_id: &str) -> bool {
+        self.intervals != new.intervals
+            || self.topics.names != new.topics.names
+            || self.zone_types != new.zone_types
+            || self.devices.get(device_id) != new.devices.get(device_id)
+    }
+
+    /// Changes in `new` that can't be applied without restarting the bridge. Log levels,
+    /// intervals, naming templates, zone types, device settings and device filters can.
+    pub fn reload_blockers(&self, new: &Config) -> Vec<&'static str> {
+        // Filters are applied live, so they don't count as account changes
+        let without_filters = |olarm: &OlarmConfig| OlarmConfig {
+            devices: DeviceFilter::default(),
+            accounts: olarm
+                .accounts
+                .iter()
+                .map(|account| OlarmAccountConfig {
+                    devices: DeviceFilter::default(),
+                    ..account.clone()
+                })
+                .collect(),
+            ..olarm.clone()
+        };
+        let logging_files = |logging: &LoggingConfig| LoggingConfig {
+            console_level: String::new(),
+            ..logging.clone()
+        };
+        [
+            (
+                logging_files(&self.logging) != logging_files(&new.logging),
+                "log files (the log files are opened at startup)",
+            ),
+            (
+                without_filters(&self.olarm) != without_filters(&new.olarm),
+                "Olarm accounts or broker (accounts log in at startup)",
+            ),
+            (
+                self.home_assistant != new.home_assistant,
+                "home_assistant (the HA broker connection is shared by everything)",
+            ),
+            (self.limits != new.limits, "limits (queues are sized at startup)"),
+            (
+                self.topics.discovery_prefix != new.topics.discovery_prefix
+                    || self.topics.base_topic != new.topics.base_topic,
+                "topic prefixes (HA commands are subscribed at startup)",
+            ),
+            (self.storage != new.storage, "storage (state files are opened at startup)"),
+            (
+                self.device_aliases() != new.device_aliases(),
+                "device aliases (HA commands are routed by alias)",
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(changed, blocker)| changed.then_some(blocker))
+        .collect()
+    }
+
+    /// Every problem with the config, so they can all be fixed at once
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut v = Validator::default();
+
+        v.check(is_valid_log_filter(&self.logging.console_level), "logging.console_level", format!(
+            "{:?} is not a log level. Use one of {} or target=level directives",
+            self.logging.console_level,
+            LOG_LEVELS.join(", ")
+        ));
+        for (key, value) in [
+            ("directory", &self.logging.directory),
+            ("debug_file", &self.logging.debug_file),
+            ("info_file", &self.logging.info_file),
+            ("warn_file", &self.logging.warn_file),
+            ("error_file", &self.logging.error_file),
+        ] {
+            v.not_empty(value, format!("logging.{}", key));
+        }
+
+        // rumqttc takes the websocket port from the URL and ignores broker_port
+        match self.olarm.broker_url.strip_prefix("wss://") {
+            None => v.error(
+                "olarm.broker_url",
+                format!("{:?} must start with wss://", self.olarm.broker_url),
+            ),
+            Some(rest) => {
+                let authority = rest.split('/').next().unwrap_or_default();
+                let url_port = authority
+                    .rsplit_once(':')
+                    .filter(|(_, port)| !port.ends_with(']'))
+                    .map(|(_, port)| port.parse::<u16>());
+                match url_port {
+                    Some(Err(_)) => v.error("olarm.broker_url", format!("{:?} has an invalid port", self.olarm.broker_url)),
+                    Some(Ok(port)) => v.check(port == self.olarm.broker_port, "olarm.broker_port", format!(
+                        "{} differs from port {} in broker_url, which is the one used",
+                        self.olarm.broker_port, port
+                    )),
+                    None => v.check(self.olarm.broker_port == 443, "olarm.broker_port", format!(
+                        "{} is ignored, add it to broker_url instead (e.g. wss://host:{})",
+                        self.olarm.broker_port, self.olarm.broker_port
+                    )),
+                }
+            }
+        }
+        v.filter(&self.olarm.devices, "olarm.devices");
+        if self.olarm.accounts.is_empty() {
+            v.credential(&self.olarm.api_token, "olarm.api_token");
+            v.credential(&self.olarm.username, "olarm.username");
+            v.credential(&self.olarm.password, "olarm.password");
+        }
+        let mut account_names = std::collections::HashSet::new();
+        for (index, account) in self.olarm.accounts.iter().enumerate() {
+            let path = format!("olarm.accounts[{}]", index);
+            let valid_name = !account.name.is_empty()
+                && account.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                // These would clash with the topics of un-namespaced devices and the bridge itself
+                && account.name != "device"
+                && account.name != "bridge";
+            v.check(valid_name, format!("{}.name", path), format!(
+                "{:?} must be letters, digits, '_' or '-', other than \"device\" or \"bridge\"",
+                account.name
+            ));
+            v.check(account_names.insert(account.name.as_str()), format!("{}.name", path), format!(
+                "{:?} is used by another account",
+                account.name
+            ));
+            v.credential(&account.api_token, format!("{}.api_token", path));
+            v.credential(&account.username, format!("{}.username", path));
+            v.credential(&account.password, format!("{}.password", path));
+            v.filter(&account.devices, format!("{}.devices", path));
+        }
+
+        let ha = &self.home_assistant;
+        v.check(!ha.mqtt_host.is_empty() && !ha.mqtt_host.contains("://"), "home_assistant.mqtt_host", format!(
+            "{:?} must be a host name or address without a scheme",
+            ha.mqtt_host
+        ));
+        v.check(ha.mqtt_port != 0, "home_assistant.mqtt_port", "must not be 0");
+        if matches!(ha.transport, BrokerTransport::Ws | BrokerTransport::Wss) {
+            v.check(ha.ws_path.starts_with('/'), "home_assistant.ws_path", format!(
+                "{:?} must start with '/'",
+                ha.ws_path
+            ));
+        }
+        for (key, file) in [
+            ("ca_file", &ha.ca_file),
+            ("client_cert_file", &ha.client_cert_file),
+            ("client_key_file", &ha.client_key_file),
+        ] {
+            if let Some(file) = file {
+                let path = format!("home_assistant.{}", key);
+                v.check(ha.uses_tls(), path.clone(), "only applies to the tls and wss transports");
+                v.check(std::path::Path::new(file).is_file(), path, format!("{:?} is not a readable file", file));
+            }
+        }
+        v.check(ha.alpn.is_empty() || ha.uses_tls(), "home_assistant.alpn", "only applies to the tls and wss transports");
+        v.check(
+            ha.client_cert_file.is_some() == ha.client_key_file.is_some(),
+            "home_assistant.client_key_file",
+            "client_cert_file and client_key_file must be set together",
+        );
+        // Without a CA bundle the system roots are used, which rumqttc can't combine with these
+        v.check(
+            ha.ca_file.is_some() || (ha.client_cert_file.is_none() && ha.alpn.is_empty()),
+            "home_assistant.ca_file",
+            "is required with client certificates or ALPN",
+        );
+        v.credential(&ha.mqtt_password, "home_assistant.mqtt_password");
+        v.not_empty(&ha.client_id, "home_assistant.client_id");
+
+        for (key, seconds) in [
+            ("status_tick_seconds", self.intervals.status_tick_seconds),
+            ("mqtt_keep_alive_seconds", self.intervals.mqtt_keep_alive_seconds),
+            ("profile_refresh_seconds", self.intervals.profile_refresh_seconds),
+        ] {
+            v.check(seconds > 0, format!("intervals.{}", key), "must be greater than 0");
+        }
+        for (key, size) in [
+            ("mqtt_queue_size", self.limits.mqtt_queue_size),
+            ("command_channel_size", self.limits.command_channel_size),
+            ("max_concurrent_commands", self.limits.max_concurrent_commands),
+        ] {
+            v.check(size > 0, format!("limits.{}", key), "must be greater than 0");
+        }
+
+        for (key, topic) in [
+            ("discovery_prefix", &self.topics.discovery_prefix),
+            ("base_topic", &self.topics.base_topic),
+        ] {
+            let valid = !topic.is_empty()
+                && !topic.contains(['+', '#'])
+                && !topic.starts_with('/')
+                && !topic.ends_with('/');
+            v.check(valid, format!("topics.{}", key), format!(
+                "{:?} must be a topic without wildcards or leading/trailing '/'",
+                topic
+            ));
+        }
+        v.not_empty(&self.storage.directory, "storage.directory");
+
+        let mut aliases = std::collections::HashSet::new();
+        for (device_id, device) in &self.devices {
+            let path = format!("devices.{}", device_id);
+            if let Some(alias) = &device.alias {
+                // The alias is a topic level, so it can't contain MQTT separators or wildcards
+                v.check(!alias.is_empty() && !alias.contains(['/', '+', '#']), format!("{}.alias", path), format!(
+                    "{:?} must not be empty or contain '/', '+' or '#'",
+                    alias
+                ));
+                v.check(aliases.insert(alias.as_str()), format!("{}.alias", path), format!(
+                    "{:?} is used by another device",
+                    alias
+                ));
+            }
+            for (index, zones) in device.area_zones.iter().enumerate() {
+                v.check(!zones.contains(&0), format!("{}.area_zones[{}]", path, index), "zone numbers start at 1");
+            }
+            for zone in device.zones.keys() {
+                v.check(zone.parse::<usize>().is_ok_and(|n| n > 0), format!("{}.zones.{}", path, zone), "must be a zone number");
+            }
+        }
+        v.errors
+    }
+
+    /// Reads the config file, then applies env var overrides, each with its `*_file` secrets
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| {
+            format!(
+                "Unable to read config file {}. Run `init-config` to create an example",
+                path
+            )
+        })?;
+        let mut table: toml::Table =
+            toml::from_str(&content).with_context(|| format!("Invalid TOML in {}", path))?;
+        apply_layers(&mut table, std::env::vars())?;
+        let config: Config = toml::Value::Table(table)
+            .try_into()
+            .with_context(|| format!("Invalid config in {} or its env overrides", path))?;
+        let errors = config.validate();
+        if !errors.is_empty() {
+            anyhow::bail!(
+                "Invalid config in {}:\n{}",
+                path,
+                errors.iter().map(|e| format!("  {}", e)).collect::<Vec<_>>().join("\n")
+            );
+        }
         Ok(config)
     }
 
+    /// Writes an example config, refusing to replace an existing file
     pub fn save_example(path: &str) -> Result<()> {
-        let example_config = Config {
+        if fs::exists(path)? {
+            anyhow::bail!("{} already exists", path);
+        }
+        let toml_content = toml::to_string_pretty(&Self::example())?;
+        fs::write(path, toml_content)?;
+        Ok(())
+    }
+
+    /// The example config, with placeholders where the user's credentials go
+    fn example() -> Config {
+        Config {
             logging: LoggingConfig {
                 directory: "./logs".to_string(),
                 debug_file: "log_debug.log".to_string(),
@@ -76,6 +712,11 @@ impl Config {
                 password: "REPLACE_WITH_YOUR_OLARM_PASSWORD".to_string(),
                 broker_url: "wss://mqtt-ws.olarm.com:443".to_string(),
                 broker_port: 443,
+                devices: DeviceFilter {
+                    include: Vec::new(),
+                    exclude: vec!["*Monitoring*".to_string()],
+                },
+                accounts: Vec::new(),
             },
             home_assistant: HomeAssistantConfig {
                 mqtt_host: "192.168.1.40".to_string(),
@@ -83,21 +724,499 @@ impl Config {
                 mqtt_username: "homeassistant".to_string(),
                 mqtt_password: "REPLACE_WITH_YOUR_HOMEASSISTANT_MQTT_PASSWORD".to_string(),
                 client_id: "olarm-forwarder".to_string(),
+                transport: BrokerTransport::Tcp,
+                ws_path: default_ws_path(),
+                ca_file: None,
+                client_cert_file: None,
+                client_key_file: None,
+                alpn: Vec::new(),
+                keep_alive_seconds: default_ha_keep_alive_seconds(),
+                clean_session: default_clean_session(),
             },
             intervals: IntervalConfig {
                 status_tick_seconds: 10,
                 reconnect_delay_seconds: 5,
                 mqtt_keep_alive_seconds: 30,
+                profile_refresh_seconds: default_profile_refresh_seconds(),
             },
             limits: LimitsConfig {
                 mqtt_queue_size: 100,
                 command_channel_size: 10,
                 max_concurrent_commands: 10,
             },
+            topics: TopicsConfig::default(),
+            storage: StorageConfig::default(),
+            zone_types: HashMap::from([
+                (
+                    "60".to_string(),
+                    ZoneTypeConfig {
+                        device_class: Some("smoke".to_string()),
+                        name: Some("Smoke Detector".to_string()),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "20".to_string(),
+                    ZoneTypeConfig {
+                        off_delay: Some(30),
+                        min_on_seconds: Some(10),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            devices: HashMap::from([(
+                "REPLACE_WITH_YOUR_DEVICE_ID".to_string(),
+                DeviceConfig {
+                    alias: Some("Home".to_string()),
+                    command_transport: CommandTransport::MqttWithRestFallback,
+                    area_zones: vec![vec![1, 2, 3], vec![4, 5]],
+                    reject_arm_when_not_ready: false,
+                    exit_delay_seconds: vec![30, 30],
+                    entry_delay_seconds: vec![30, 30],
+                    zones: HashMap::from([(
+                        "4".to_string(),
+                        ZoneOverride {
+                            device_class: Some("vibration".to_string()),
+                            name: Some("Safe".to_string()),
+                            icon: Some("mdi:safe".to_string()),
+                            hide: false,
+                            off_delay: Some(5),
+                            min_on_seconds: None,
+                            min_off_seconds: Some(2),
+                        },
+                    )]),
+                },
+            )]),
+        }
+    }
+}
+
+/// A config value that failed validation
+#[derive(Debug, Clone)]
+pub struct ConfigError {
+    /// e.g. `limits.command_channel_size`
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Default)]
+struct Validator {
+    errors: Vec<ConfigError>,
+}
+
+impl Validator {
+    fn error(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.errors.push(ConfigError {
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+
+    fn check(&mut self, valid: bool, path: impl Into<String>, message: impl Into<String>) {
+        if !valid {
+            self.error(path, message);
+        }
+    }
+
+    fn not_empty(&mut self, value: &str, path: impl Into<String>) {
+        self.check(!value.is_empty(), path, "must not be empty");
+    }
+
+    /// Set, and not left at the example config's placeholder
+    fn credential(&mut self, value: &str, path: impl Into<String>) {
+        let path = path.into();
+        if value.starts_with("REPLACE_WITH_") {
+            self.error(path, "is still the example placeholder");
+        } else {
+            self.not_empty(value, path);
+        }
+    }
+
+    fn filter(&mut self, filter: &DeviceFilter, path: impl Into<String>) {
+        let path = path.into();
+        for (key, patterns) in [("include", &filter.include), ("exclude", &filter.exclude)] {
+            self.check(!patterns.iter().any(String::is_empty), format!("{}.{}", path, key), "patterns must not be empty");
+        }
+    }
+}
+
+const LOG_LEVELS: [&str; 6] = ["trace", "debug", "info", "warn", "error", "off"];
+
+/// A level, or comma-separated `target=level` directives
+fn is_valid_log_filter(filter: &str) -> bool {
+    filter.split(',').all(|directive| {
+        let level = directive.rsplit_once('=').map_or(directive, |(_, level)| level);
+        LOG_LEVELS.contains(&level.trim().to_lowercase().as_str())
+    })
+}
+
+/// Applies the env overrides over the config file's table, then reads the `*_file` secrets. An
+/// env secret replaces the file's however either is given, e.g.
+/// `OLARM_BRIDGE__HOME_ASSISTANT__MQTT_PASSWORD_FILE` replaces `mqtt_password` from the file.
+fn apply_layers(table: &mut toml::Table, vars: impl Iterator<Item = (String, String)>) -> Result<()> {
+    apply_env_overrides(table, vars)?;
+    resolve_secret_files(table)
+}
+
+/// `<key>_file` for a secret key, and `<key>` for its `_file` variant
+fn secret_counterpart(key: &str) -> Option<String> {
+    if SECRET_KEYS.contains(&key) {
+        return Some(format!("{}_file", key));
+    }
+    key.strip_suffix("_file")
+        .filter(|secret_key| SECRET_KEYS.contains(secret_key))
+        .map(str::to_string)
+}
+
+/// Sets e.g. `OLARM_BRIDGE__HOME_ASSISTANT__MQTT_HOST=10.0.0.2` as `home_assistant.mqtt_host`.
+/// Numeric levels index lists, e.g. `OLARM_BRIDGE__OLARM__ACCOUNTS__0__PASSWORD`.
+/// Values are parsed as TOML unless they replace a string, so quote a value to force a string.
+fn apply_env_overrides(
+    table: &mut toml::Table,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<()> {
+    let mut root = toml::Value::Table(std::mem::take(table));
+    let mut applied = std::collections::HashMap::new();
+    for (name, raw_value) in vars {
+        let Some(path) = name.strip_prefix(ENV_OVERRIDE_PREFIX) else {
+            continue;
         };
+        let keys = path.split("__").map(str::to_lowercase).collect::<Vec<_>>();
+        if keys.iter().any(String::is_empty) {
+            anyhow::bail!("Invalid config override {}", name);
+        }
+        // A secret and its file in the same layer leave no way to tell which is meant
+        if let Some((last, parents)) = keys.split_last()
+            && let Some(counterpart) = secret_counterpart(last)
+            && let Some(other) = applied.get(&[parents, &[counterpart]].concat())
+        {
+            anyhow::bail!("Both {} and {} are set, use one", other, name);
+        }
+        set_override(&mut root, &keys, raw_value)
+            .with_context(|| format!("Unable to apply config override {}", name))?;
+        applied.insert(keys, name);
+    }
+    if let toml::Value::Table(root) = root {
+        *table = root;
+    }
+    Ok(())
+}
 
-        let toml_content = toml::to_string_pretty(&example_config)?;
-        fs::write(path, toml_content)?;
-        Ok(())
+fn set_override(value: &mut toml::Value, keys: &[String], raw_value: String) -> Result<()> {
+    let (key, rest) = keys.split_first().expect("override paths are not empty");
+    let child = match value {
+        toml::Value::Table(table) => {
+            // Replacing the file's secret, however it was given there
+            if rest.is_empty()
+                && let Some(counterpart) = secret_counterpart(key)
+            {
+                table.remove(&counterpart);
+            }
+            if rest.is_empty() && !table.contains_key(key.as_str()) {
+                table.insert(key.clone(), parse_env_value(raw_value));
+                return Ok(());
+            }
+            table
+                .entry(key.as_str())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        }
+        toml::Value::Array(items) => {
+            let index = key
+                .parse::<usize>()
+                .with_context(|| format!("{} is not an index into the list", key))?;
+            let len = items.len();
+            items.get_mut(index).ok_or_else(|| {
+                anyhow::anyhow!("Index {} is out of range, the list has {} items", index, len)
+            })?
+        }
+        _ => anyhow::bail!("{} is neither a section nor a list", key),
+    };
+    if rest.is_empty() {
+        *child = match child {
+            toml::Value::String(_) => toml::Value::String(raw_value),
+            _ => parse_env_value(raw_value),
+        };
+        return Ok(());
+    }
+    set_override(child, rest, raw_value)
+}
+
+/// `123`, `true` or `["a", "b"]` become TOML values, anything else a string
+fn parse_env_value(raw_value: String) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", raw_value))
+        .ok()
+        .and_then(|mut parsed| parsed.remove("value"))
+        .unwrap_or(toml::Value::String(raw_value))
+}
+
+/// Replaces every `<key>_file` of a secret key with `<key>` set to the file's content
+fn resolve_secret_files(table: &mut toml::Table) -> Result<()> {
+    for key in SECRET_KEYS {
+        let file_key = format!("{}_file", key);
+        if let Some(file_value) = table.remove(&file_key) {
+            if table.contains_key(key) {
+                anyhow::bail!("Both {} and {} are set, use one", key, file_key);
+            }
+            let file_path = file_value
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("{} must be a path", file_key))?;
+            let secret = fs::read_to_string(file_path)
+                .with_context(|| format!("Unable to read {} {}", file_key, file_path))?;
+            table.insert(key.to_string(), toml::Value::String(secret.trim_end().to_string()));
+        }
     }
-}
\ No newline at end of file
+    for (_, value) in table.iter_mut() {
+        match value {
+            toml::Value::Table(child) => resolve_secret_files(child)?,
+            toml::Value::Array(items) => {
+                for child in items.iter_mut().filter_map(toml::Value::as_table_mut) {
+                    resolve_secret_files(child)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> DeviceFilter {
+        DeviceFilter {
+            include: include.iter().map(|pattern| pattern.to_string()).collect(),
+            exclude: exclude.iter().map(|pattern| pattern.to_string()).collect(),
+        }
+    }
+
+    const HOME: [&str; 4] = ["abc123", "861234567890123", "SN0042", "Home Monitoring"];
+
+    #[test]
+    fn glob_match_wildcards() {
+        assert!(glob_match("home", "home"));
+        assert!(!glob_match("home", "homes"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*monitoring*", "home monitoring unit"));
+        assert!(glob_match("sn00?2", "sn0042"));
+        assert!(!glob_match("sn00?2", "sn002"));
+        assert!(glob_match("a*b*c", "axxbyybc"));
+        assert!(!glob_match("a*b*c", "axxbyyb"));
+    }
+
+    #[test]
+    fn empty_filter_allows_every_device() {
+        assert!(filter(&[], &[]).allows_identifiers(&HOME));
+    }
+
+    #[test]
+    fn include_matches_any_identifier_ignoring_case() {
+        assert!(filter(&["ABC123"], &[]).allows_identifiers(&HOME));
+        assert!(filter(&["8612*"], &[]).allows_identifiers(&HOME));
+        assert!(filter(&["sn0042"], &[]).allows_identifiers(&HOME));
+        assert!(filter(&["*monitoring*"], &[]).allows_identifiers(&HOME));
+        assert!(!filter(&["Office*"], &[]).allows_identifiers(&HOME));
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        assert!(!filter(&[], &["*Monitoring"]).allows_identifiers(&HOME));
+        assert!(!filter(&["abc123"], &["SN0042"]).allows_identifiers(&HOME));
+        assert!(filter(&["abc123"], &["Office*"]).allows_identifiers(&HOME));
+    }
+
+    fn overridden(toml: &str, vars: &[(&str, &str)]) -> Result<toml::Table> {
+        let mut table = toml::from_str::<toml::Table>(toml).unwrap();
+        let vars = vars.iter().map(|(name, value)| (name.to_string(), value.to_string()));
+        apply_layers(&mut table, vars.collect::<Vec<_>>().into_iter())?;
+        Ok(table)
+    }
+
+    #[test]
+    fn parse_env_value_reads_toml_or_falls_back_to_a_string() {
+        assert_eq!(parse_env_value("123".to_string()), toml::Value::Integer(123));
+        assert_eq!(parse_env_value("true".to_string()), toml::Value::Boolean(true));
+        assert_eq!(
+            parse_env_value(r#"["a", "b"]"#.to_string()),
+            toml::Value::Array(vec!["a".into(), "b".into()])
+        );
+        assert_eq!(parse_env_value(r#""123""#.to_string()), toml::Value::String("123".to_string()));
+        assert_eq!(parse_env_value("10.0.0.2".to_string()), toml::Value::String("10.0.0.2".to_string()));
+    }
+
+    #[test]
+    fn env_overrides_set_nested_keys_and_list_items() {
+        let table = overridden(
+            "[home_assistant]\nmqtt_host = \"localhost\"\n[[olarm.accounts]]\npassword = \"old\"\n",
+            &[
+                ("OLARM_BRIDGE__HOME_ASSISTANT__MQTT_HOST", "10.0.0.2"),
+                ("OLARM_BRIDGE__HOME_ASSISTANT__MQTT_PORT", "1884"),
+                ("OLARM_BRIDGE__OLARM__ACCOUNTS__0__PASSWORD", "1234"),
+                ("UNRELATED", "ignored"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(table["home_assistant"]["mqtt_host"].as_str(), Some("10.0.0.2"));
+        assert_eq!(table["home_assistant"]["mqtt_port"].as_integer(), Some(1884));
+        // Replacing a string keeps it a string
+        assert_eq!(table["olarm"]["accounts"][0]["password"].as_str(), Some("1234"));
+        assert!(!table.contains_key("unrelated"));
+    }
+
+    #[test]
+    fn env_overrides_reject_bad_paths() {
+        let toml = "[[olarm.accounts]]\npassword = \"old\"\n";
+        assert!(overridden(toml, &[("OLARM_BRIDGE__OLARM__ACCOUNTS__1__PASSWORD", "x")]).is_err());
+        assert!(overridden(toml, &[("OLARM_BRIDGE__OLARM__ACCOUNTS__FIRST__PASSWORD", "x")]).is_err());
+        assert!(overridden(toml, &[("OLARM_BRIDGE__OLARM____PASSWORD", "x")]).is_err());
+    }
+
+    /// The example config with its placeholders filled in
+    fn valid_config() -> Config {
+        let mut config = Config::example();
+        config.olarm.api_token = "token".to_string();
+        config.olarm.username = "user@example.com".to_string();
+        config.olarm.password = "secret".to_string();
+        config.home_assistant.mqtt_password = "secret".to_string();
+        config
+    }
+
+    fn error_paths(config: &Config) -> Vec<String> {
+        config.validate().into_iter().map(|error| error.path).collect()
+    }
+
+    #[test]
+    fn valid_config_has_no_errors() {
+        assert_eq!(error_paths(&valid_config()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn example_placeholders_are_errors() {
+        let paths = error_paths(&Config::example());
+        for path in ["olarm.api_token", "olarm.username", "olarm.password", "home_assistant.mqtt_password"] {
+            assert!(paths.iter().any(|error_path| error_path == path), "{} not reported in {:?}", path, paths);
+        }
+    }
+
+    #[test]
+    fn bad_values_are_errors() {
+        let mut config = valid_config();
+        config.olarm.broker_url = "mqtt-ws.olarm.com:443".to_string();
+        assert_eq!(error_paths(&config), ["olarm.broker_url"]);
+
+        let mut config = valid_config();
+        config.limits.command_channel_size = 0;
+        assert_eq!(error_paths(&config), ["limits.command_channel_size"]);
+
+        let mut config = valid_config();
+        config.olarm.accounts = ["device", "home", "home"]
+            .into_iter()
+            .map(|name| OlarmAccountConfig {
+                name: name.to_string(),
+                api_token: "token".to_string(),
+                username: "user@example.com".to_string(),
+                password: "secret".to_string(),
+                devices: DeviceFilter::default(),
+            })
+            .collect();
+        assert_eq!(error_paths(&config), ["olarm.accounts[0].name", "olarm.accounts[2].name"]);
+    }
+
+    #[test]
+    fn filter_and_live_setting_changes_need_no_restart() {
+        let old = valid_config();
+        let mut new = old.clone();
+        new.olarm.devices.include = vec!["Home*".to_string()];
+        new.logging.console_level = "info".to_string();
+        new.intervals.status_tick_seconds = 30;
+        new.topics.names.zone = "Zone {zone}".to_string();
+        assert!(old.reload_blockers(&new).is_empty());
+    }
+
+    #[test]
+    fn connection_and_routing_changes_need_a_restart() {
+        let old = valid_config();
+
+        let mut new = old.clone();
+        new.home_assistant.mqtt_port = 1883;
+        assert_eq!(old.reload_blockers(&new).len(), 1);
+
+        let mut new = old.clone();
+        new.olarm.password = "changed".to_string();
+        assert_eq!(old.reload_blockers(&new).len(), 1);
+
+        let mut new = old.clone();
+        new.devices.get_mut("REPLACE_WITH_YOUR_DEVICE_ID").unwrap().alias = Some("Office".to_string());
+        assert_eq!(old.reload_blockers(&new).len(), 1);
+    }
+
+    #[test]
+    fn device_changes_affect_only_that_device() {
+        let old = valid_config();
+        let mut new = old.clone();
+        new.devices
+            .get_mut("REPLACE_WITH_YOUR_DEVICE_ID")
+            .unwrap()
+            .reject_arm_when_not_ready = true;
+        assert!(old.affects_device(&new, "REPLACE_WITH_YOUR_DEVICE_ID"));
+        assert!(!old.affects_device(&new, "other"));
+
+        new.devices.insert("other".to_string(), DeviceConfig::default());
+        assert!(old.affects_device(&new, "other"));
+    }
+
+    #[test]
+    fn shared_changes_affect_every_device() {
+        let old = valid_config();
+        let mut new = old.clone();
+        new.intervals.status_tick_seconds = 30;
+        assert!(old.affects_device(&new, "REPLACE_WITH_YOUR_DEVICE_ID"));
+        assert!(old.affects_device(&new, "other"));
+
+        let mut new = old.clone();
+        new.olarm.devices.include = vec!["Home*".to_string()];
+        assert!(!old.affects_device(&new, "other"));
+    }
+
+    #[test]
+    fn env_secret_file_replaces_the_config_file_secret() {
+        let secret_path = std::env::temp_dir().join(format!("olarm_secret_{}", std::process::id()));
+        fs::write(&secret_path, "from secret\n").unwrap();
+        let table = overridden(
+            "[home_assistant]\nmqtt_password = \"from file\"\n",
+            &[("OLARM_BRIDGE__HOME_ASSISTANT__MQTT_PASSWORD_FILE", secret_path.to_str().unwrap())],
+        );
+        fs::remove_file(&secret_path).unwrap();
+        let table = table.unwrap();
+        assert_eq!(table["home_assistant"]["mqtt_password"].as_str(), Some("from secret"));
+        assert!(!table["home_assistant"].as_table().unwrap().contains_key("mqtt_password_file"));
+    }
+
+    #[test]
+    fn env_secret_replaces_the_config_file_secret_file() {
+        // The file's secret file isn't read, so it needn't exist
+        let table = overridden(
+            "[home_assistant]\nmqtt_password_file = \"/nonexistent/secret\"\n",
+            &[("OLARM_BRIDGE__HOME_ASSISTANT__MQTT_PASSWORD", "from env")],
+        )
+        .unwrap();
+        assert_eq!(table["home_assistant"]["mqtt_password"].as_str(), Some("from env"));
+    }
+
+    #[test]
+    fn secret_and_secret_file_in_the_same_layer_are_rejected() {
+        let in_file = "[home_assistant]\nmqtt_password = \"a\"\nmqtt_password_file = \"/nonexistent/secret\"\n";
+        assert!(overridden(in_file, &[]).unwrap_err().to_string().starts_with("Both"));
+        let in_env = [
+            ("OLARM_BRIDGE__HOME_ASSISTANT__MQTT_PASSWORD", "a"),
+            ("OLARM_BRIDGE__HOME_ASSISTANT__MQTT_PASSWORD_FILE", "/nonexistent/secret"),
+        ];
+        assert!(overridden("[home_assistant]\n", &in_env).unwrap_err().to_string().starts_with("Both"));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::config::Config;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{Notify, watch};
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::{EnvFilter, Registry, reload};
+
+/// How often the config file's modification time is checked. Polling also works for
+/// bind-mounted files in Docker, where change notifications are unreliable.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Reloads the config file when it changes or on SIGHUP. Changes that can be applied live are
+/// published on `config_tx`; the rest are rejected and the running config is kept.
+pub struct ConfigWatcher {
+    pub path: String,
+    pub config_tx: watch::Sender<Arc<Config>>,
+    pub console_filter: reload::Handle<EnvFilter, Registry>,
+}
+
+impl ConfigWatcher {
+    pub async fn run(self) {
+        let reload_requested = Arc::new(Notify::new());
+        Self::forward_hangups(reload_requested.clone());
+
+        let mut tick = tokio::time::interval(POLL_INTERVAL);
+        let mut modified = Self::modified(&self.path);
+        loop {
+            tokio::select! {
+                _ = reload_requested.notified() => {
+                    info!("Received SIGHUP, reloading {}", self.path);
+                }
+                _ = tick.tick() => {
+                    let current = Self::modified(&self.path);
+                    if current == modified {
+                        continue;
+                    }
+                    modified = current;
+                    info!("{} changed, reloading", self.path);
+                }
+            }
+            self.reload();
+        }
+    }
+
+    fn reload(&self) {
+        let new_config = match Config::load(&self.path) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("Keeping the running config, reload failed: {:#}", e);
+                return;
+            }
+        };
+        let current = self.config_tx.borrow().clone();
+        if *current == new_config {
+            debug!("Config is unchanged");
+            return;
+        }
+        let blockers = current.reload_blockers(&new_config);
+        if !blockers.is_empty() {
+            warn!(
+                "Rejected config reload, restart the bridge to change: {}",
+                blockers.join("; ")
+            );
+            return;
+        }
+
+        if current.logging.console_level != new_config.logging.console_level {
+            let level = new_config.logging.console_level.clone();
+            if let Err(e) = self.console_filter.modify(|filter| *filter = EnvFilter::new(&level)) {
+                error!("Failed to change the console log level: {:?}", e);
+            }
+        }
+        self.config_tx.send_replace(Arc::new(new_config));
+        info!("Applied reloaded config");
+    }
+
+    fn modified(path: &str) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    #[cfg(unix)]
+    fn forward_hangups(reload_requested: Arc<Notify>) {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::hangup()) {
+            Ok(mut hangups) => {
+                tokio::spawn(async move {
+                    while hangups.recv().await.is_some() {
+                        reload_requested.notify_one();
+                    }
+                });
+            }
+            Err(e) => error!("Unable to listen for SIGHUP: {:?}", e),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn forward_hangups(_reload_requested: Arc<Notify>) {}
+}
@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
 use crate::home_assistant::availability::{Availability, AvailabilityMode};
+use crate::home_assistant::device::Device;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct AlarmControlPanelDiscoveryPayload {
+    pub device: Device,
     pub name: String,
     pub unique_id: String,
     pub state_topic: String,
@@ -12,6 +14,8 @@ pub struct AlarmControlPanelDiscoveryPayload {
     pub payload_arm_night: String,
     pub payload_disarm: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload_arm_custom_bypass: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub code: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub code_arm_required: Option<bool>,
@@ -1,11 +1,12 @@
-pub(crate) use crate::home_assistant::device::Device;
+use crate::home_assistant::device::Device;
 use serde::{Deserialize, Serialize};
 use crate::home_assistant::availability::{Availability, AvailabilityMode};
 
 #[derive(Serialize, Deserialize)]
 pub struct BinarySensorDiscoveryPayload {
     pub device: Device,
-    pub device_class: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_class: Option<String>,
     pub name: String,
     pub payload_off: String,
     pub payload_on: String,
@@ -14,6 +15,10 @@ pub struct BinarySensorDiscoveryPayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub off_delay: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity_category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub json_attributes_topic: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub availability: Option<Vec<Availability>>,
@@ -0,0 +1,23 @@
+use crate::home_assistant::device::Device;
+use serde::{Deserialize, Serialize};
+use crate::home_assistant::availability::{Availability, AvailabilityMode};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ButtonDiscoveryPayload {
+    pub device: Device,
+    pub name: String,
+    pub unique_id: String,
+    pub command_topic: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload_press: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity_category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_attributes_topic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub availability: Option<Vec<Availability>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub availability_mode: Option<AvailabilityMode>,
+}
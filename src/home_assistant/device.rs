@@ -1,4 +1,10 @@
 ﻿use serde::{Deserialize, Serialize};
+use crate::olarm_api::models::response::user_response::UserDevice;
+use crate::topics::TopicBuilder;
+
+/// Identifier of the bridge's own device, which every Olarm unit is connected through
+pub const BRIDGE_DEVICE_IDENTIFIER: &str = "olarm_mqtt_bridge";
+const OLARM_CONFIGURATION_URL: &str = "https://user.olarm.com";
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Device {
@@ -6,4 +12,56 @@ pub struct Device {
     pub manufacturer: String,
     pub model: String,
     pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sw_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub via_device: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub configuration_url: Option<String>,
+}
+
+impl Device {
+    /// The HA device registry entry for the bridge process itself
+    pub fn bridge() -> Self {
+        Self {
+            identifiers: vec![BRIDGE_DEVICE_IDENTIFIER.to_string()],
+            manufacturer: "Daniel van Schoor".to_string(),
+            model: "Olarm MQTT Bridge".to_string(),
+            name: "Olarm MQTT Bridge".to_string(),
+            sw_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            serial_number: None,
+            via_device: None,
+            configuration_url: None,
+        }
+    }
+
+    /// The HA device registry entry shared by every entity of an Olarm unit
+    pub fn from_user_device(device: &UserDevice, topics: &TopicBuilder) -> Self {
+        Self {
+            identifiers: vec![topics.device_key(&device.imei), topics.device_key(&device.id)],
+            manufacturer: "Daniel van Schoor".to_string(),
+            model: device.alarm_type_detail.clone(),
+            name: topics.device_name(device),
+            sw_version: Some(device.firmware.clone()),
+            serial_number: Some(device.serial.clone()),
+            via_device: Some(BRIDGE_DEVICE_IDENTIFIER.to_string()),
+            configuration_url: Some(OLARM_CONFIGURATION_URL.to_string()),
+        }
+    }
+
+    /// A sub-device for one area of an Olarm unit, connected through the unit's device
+    pub fn for_area(device: &UserDevice, area_number: usize, area_label: &str, topics: &TopicBuilder) -> Self {
+        Self {
+            identifiers: vec![format!("{}_area_{}", topics.device_key(&device.id), area_number)],
+            manufacturer: "Daniel van Schoor".to_string(),
+            model: format!("{} Area", device.alarm_type_detail),
+            name: topics.area_name(device, area_number, area_label),
+            sw_version: None,
+            serial_number: None,
+            via_device: Some(topics.device_key(&device.id)),
+            configuration_url: Some(OLARM_CONFIGURATION_URL.to_string()),
+        }
+    }
 }
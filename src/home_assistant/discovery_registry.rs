@@ -0,0 +1,134 @@
+use crate::persist;
+use dashmap::DashMap;
+use rumqttc::QoS;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Remembers every discovery config the bridge has published, per device, so entities that
+/// no longer exist can be removed from HA. Persisted so this survives restarts.
+pub struct DiscoveryRegistry {
+    path: PathBuf,
+    published: DashMap<String, BTreeSet<String>>,
+    /// Topics republished since a sweep started, keyed by device id
+    sweeps: DashMap<String, BTreeSet<String>>,
+    /// Held while saving, so snapshots reach the file in the order they were taken
+    save_lock: Mutex<()>,
+}
+
+impl DiscoveryRegistry {
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let published = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str::<HashMap<String, BTreeSet<String>>>(&content)
+                .unwrap_or_else(|e| {
+                    warn!("Ignoring unreadable discovery registry {:?}: {:?}", path, e);
+                    HashMap::new()
+                }),
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            path,
+            published: published.into_iter().collect(),
+            sweeps: DashMap::new(),
+            save_lock: Mutex::new(()),
+        }
+    }
+
+    /// Publishes a retained discovery config and records it against the device
+    pub async fn publish(
+        &self,
+        ha_client: &rumqttc::AsyncClient,
+        device_id: &str,
+        topic: &str,
+        payload: String,
+    ) -> anyhow::Result<()> {
+        ha_client.publish(topic, QoS::AtLeastOnce, true, payload).await?;
+
+        if let Some(mut sweep) = self.sweeps.get_mut(device_id) {
+            sweep.insert(topic.to_string());
+        }
+        let inserted = self
+            .published
+            .entry(device_id.to_string())
+            .or_default()
+            .insert(topic.to_string());
+        if inserted {
+            self.save().await;
+        }
+        Ok(())
+    }
+
+    /// Starts collecting the device's discovery topics as they are republished
+    pub fn begin_sweep(&self, device_id: &str) {
+        self.sweeps.insert(device_id.to_string(), BTreeSet::new());
+    }
+
+    /// Removes every entity of the device that was not republished since `begin_sweep`
+    pub async fn finish_sweep(
+        &self,
+        ha_client: &rumqttc::AsyncClient,
+        device_id: &str,
+    ) -> anyhow::Result<()> {
+        let Some((_, current)) = self.sweeps.remove(device_id) else {
+            return Ok(());
+        };
+        let stale = self
+            .published
+            .get(device_id)
+            .map(|published| published.difference(&current).cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        for topic in &stale {
+            info!("Removing stale discovery config {}", topic);
+            ha_client.publish(topic, QoS::AtLeastOnce, true, Vec::new()).await?;
+        }
+        self.published.insert(device_id.to_string(), current);
+        self.save().await;
+        Ok(())
+    }
+
+    /// Removes every entity ever published for the device. Returns how many were removed.
+    pub async fn purge(
+        &self,
+        ha_client: &rumqttc::AsyncClient,
+        device_id: &str,
+    ) -> anyhow::Result<usize> {
+        let topics = self.topics(device_id);
+        for topic in &topics {
+            ha_client.publish(topic, QoS::AtLeastOnce, true, Vec::new()).await?;
+        }
+        self.published.remove(device_id);
+        self.sweeps.remove(device_id);
+        self.save().await;
+        Ok(topics.len())
+    }
+
+    /// Whether a discovery config was published on `topic` for the device and not removed since
+    pub fn is_published(&self, device_id: &str, topic: &str) -> bool {
+        self.published
+            .get(device_id)
+            .is_some_and(|topics| topics.contains(topic))
+    }
+
+    pub fn topics(&self, device_id: &str) -> Vec<String> {
+        self.published
+            .get(device_id)
+            .map(|topics| topics.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    async fn save(&self) {
+        let _saving = self.save_lock.lock().await;
+        let snapshot: HashMap<String, BTreeSet<String>> = self
+            .published
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        if let Err(e) = persist::write_json(&self.path, &snapshot).await {
+            warn!("Failed to save discovery registry {:?}: {:?}", self.path, e);
+        }
+    }
+}
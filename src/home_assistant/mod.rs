@@ -1,6 +1,9 @@
 pub mod alarm_control_panel;
 pub mod binary_sensor;
-mod device;
+pub mod button;
+pub mod device;
+pub mod discovery_registry;
+pub mod sensor;
 pub mod switch;
 pub mod models;
 pub mod availability;
@@ -1 +1,2 @@
-﻿pub mod requests;
\ No newline at end of file
+﻿pub mod requests;
+pub mod responses;
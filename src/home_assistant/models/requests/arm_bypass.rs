@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use crate::olarm_api::models::request::actions_request::ActionCmd;
+
+/// Payload HA's alarm panel sends when `arm_custom_bypass` is selected
+pub const ARM_CUSTOM_BYPASS_PAYLOAD: &str = "ARM_CUSTOM_BYPASS";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ArmBypassRequest {
+    /// Arm mode to use once the open zones have been bypassed
+    #[serde(default = "default_arm_cmd")]
+    pub action_cmd: ActionCmd,
+}
+
+fn default_arm_cmd() -> ActionCmd {
+    ActionCmd::AreaArm
+}
+
+impl ArmBypassRequest {
+    pub fn new(action_cmd: ActionCmd) -> Self {
+        Self { action_cmd }
+    }
+
+    pub fn to_payload(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
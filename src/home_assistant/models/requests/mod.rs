@@ -1 +1,2 @@
-﻿pub mod zone_bypass;
\ No newline at end of file
+﻿pub mod zone_bypass;
+pub mod arm_bypass;
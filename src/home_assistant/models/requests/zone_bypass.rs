@@ -1,4 +1,5 @@
 ﻿use serde::{Deserialize, Serialize};
+use crate::olarm_api::models::request::actions_request::ActionCmd;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ZoneBypassRequest {
@@ -12,6 +13,15 @@ impl ZoneBypassRequest {
     pub fn to_payload(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
+
+    /// The Olarm action that applies this request
+    pub fn action_cmd(&self) -> ActionCmd {
+        if self.bypass {
+            ActionCmd::ZoneBypass
+        } else {
+            ActionCmd::ZoneUnBypass
+        }
+    }
     
 }
 
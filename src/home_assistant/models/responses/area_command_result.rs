@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+use crate::olarm_api::models::request::actions_request::ActionCmd;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BypassedZone {
+    pub zone_number: usize,
+    pub name: String,
+}
+
+/// Outcome of a command sent to an area, published to the area's result topic
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AreaCommandResult {
+    pub action_cmd: ActionCmd,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bypassed_zones: Vec<BypassedZone>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub timestamp: String,
+}
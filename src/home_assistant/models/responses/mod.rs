@@ -0,0 +1 @@
+pub mod area_command_result;
@@ -0,0 +1,27 @@
+use crate::home_assistant::device::Device;
+use serde::{Deserialize, Serialize};
+use crate::home_assistant::availability::{Availability, AvailabilityMode};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SensorDiscoveryPayload {
+    pub device: Device,
+    pub name: String,
+    pub unique_id: String,
+    pub state_topic: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit_of_measurement: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity_category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_attributes_topic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub availability: Option<Vec<Availability>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub availability_mode: Option<AvailabilityMode>,
+}
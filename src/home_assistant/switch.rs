@@ -1,4 +1,4 @@
-﻿use crate::home_assistant::binary_sensor::Device;
+﻿use crate::home_assistant::device::Device;
 use serde::{Deserialize, Serialize};
 use crate::home_assistant::availability::{Availability, AvailabilityMode};
 
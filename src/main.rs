@@ -1,15 +1,25 @@
 #![recursion_limit = "256"]
+mod cli;
+mod commands;
 mod config;
+mod config_watcher;
 mod home_assistant;
 pub mod olarm_api;
+mod persist;
 mod processors;
 mod throttled_mqtt_client;
+mod topics;
 
-use tracing::{debug, error, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 use tracing_appender::rolling;
-use tracing_subscriber::{EnvFilter, Layer, fmt, layer::SubscriberExt};
+use tracing_subscriber::{EnvFilter, Layer, fmt, layer::SubscriberExt, reload};
 
-use crate::config::Config;
+use crate::cli::{Cli, USAGE};
+use crate::config::{BrokerTransport, Config, HomeAssistantConfig};
+use crate::config_watcher::ConfigWatcher;
+use crate::home_assistant::models::requests::arm_bypass::{
+    ARM_CUSTOM_BYPASS_PAYLOAD, ArmBypassRequest,
+};
 use crate::home_assistant::models::requests::zone_bypass::ZoneBypassRequest;
 use crate::olarm_api::cached_olarm_client::CachedOlarmClient;
 use crate::olarm_api::models::device_profile::DeviceProfile;
@@ -17,33 +27,63 @@ use crate::olarm_api::models::request::actions_request::{ActionCmd, MqttRequest}
 use crate::olarm_api::models::response::mqtt_device_response::MqttDeviceResponse;
 use crate::olarm_api::models::response::mqtt_wifi_response::MqttWifiResponse;
 use crate::olarm_api::models::response::user_response::UserDevice;
+use crate::olarm_api::models::zone_status::ZoneStatus;
 use crate::olarm_api::olarm_client::{OlarmApiTrait, OlarmClient};
+use crate::home_assistant::availability::AvailabilityState;
+use crate::home_assistant::device::Device;
+use crate::home_assistant::discovery_registry::DiscoveryRegistry;
+use crate::processors::bridge_processor::{
+    BridgeProcessor, BridgeState,
+};
+use crate::processors::outputs_processor::OutputsProcessor;
+use crate::processors::triggers_processor::TriggersProcessor;
 use crate::processors::panel_processor::PanelProcessor;
+use crate::processors::trip_counters::TripCounters;
 use crate::processors::zones_processor::ZonesProcessor;
 use crate::processors::{MqttDeviceResponseProcessor, ProcessorState};
 use crate::throttled_mqtt_client::MqttThrottledClient;
-use chrono::{DateTime, Utc};
+use crate::topics::TopicBuilder;
+use anyhow::Context;
+use chrono::{DateTime, Local, Utc};
 use dashmap::DashSet;
 use processors::ha_processor::HaProcessor;
-use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS, TlsConfiguration, Transport};
+use rumqttc::{
+    AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS, TlsConfiguration, Transport,
+};
 use std::collections::HashMap;
 use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::join;
 use tokio::sync::mpsc::Receiver;
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::{RwLock, mpsc, watch};
+use tokio::task::JoinHandle;
 use tracing_subscriber::util::SubscriberInitExt;
 
 #[tokio::main]
 pub async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse()?;
+    match cli.command.as_deref() {
+        Some("init-config") => {
+            Config::save_example(&cli.config_path)?;
+            println!(
+                "Wrote an example config to {}. Edit it with your settings and start the bridge.",
+                cli.config_path
+            );
+            return Ok(());
+        }
+        Some("check-config") => return check_config(&cli).await,
+        Some("help" | "--help" | "-h") => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Some(command) if commands::is_command(command) => return commands::run(&cli).await,
+        Some("purge-discovery") | None => {}
+        Some(command) => anyhow::bail!("Unknown command {}\n\n{}", command, USAGE),
+    }
+
     // Load configuration
-    let config = Config::from_file("config.toml").or_else(|e| {
-        println!("Config file not found. Creating example config.toml...");
-        Config::save_example("config.toml")?;
-        println!("Please edit config.toml with your settings and restart the application.");
-        Err(e)
-    })?;
+    let config = Config::load(&cli.config_path)?;
 
     // Directory for logs
     let log_dir = &config.logging.directory;
@@ -75,10 +115,10 @@ pub async fn main() -> anyhow::Result<()> {
         .with_ansi(false)
         .with_filter(tracing_subscriber::filter::LevelFilter::ERROR);
 
-    // Console pretty logger (like pretty_env_logger)
-    let console_layer = fmt::layer()
-        .pretty()
-        .with_filter(EnvFilter::new(&config.logging.console_level));
+    // Console pretty logger (like pretty_env_logger), its level can be changed by a config reload
+    let (console_filter, console_filter_handle) =
+        reload::Layer::new(EnvFilter::new(&config.logging.console_level));
+    let console_layer = fmt::layer().pretty().with_filter(console_filter);
 
     // Compose subscriber
     tracing_subscriber::registry()
@@ -89,39 +129,89 @@ pub async fn main() -> anyhow::Result<()> {
         .with(error_layer)
         .init();
 
-    // Let's get our JWT access token first
-    let olarm_client = Arc::new(CachedOlarmClient::new(OlarmClient::new(
-        config.olarm.api_token.clone(),
-        &config.olarm.username,
-        &config.olarm.password,
-    )));
+    let topics = Arc::new(TopicBuilder::new(config.topics.clone()));
+    let mut ha_options = ha_mqtt_options(&config, &config.home_assistant.client_id)?;
+    ha_options.set_last_will(LastWill::new(
+        topics.bridge("availability"),
+        AvailabilityState::Offline.as_serde_value(),
+        QoS::AtLeastOnce,
+        true,
+    ));
+    let (ha_client, mut ha_eventloop) =
+        AsyncClient::new(ha_options, config.limits.command_channel_size);
 
-    let login_response = olarm_client.get_oauth_response().await?; //todo: retries
+    let discovery = Arc::new(DiscoveryRegistry::load(
+        config.storage.discovery_registry_path(),
+    ));
 
-    let user_devices = olarm_client
-        .get_user(&login_response.user_index.to_string())
-        .await?;
+    if cli.command.as_deref() == Some("purge-discovery") {
+        let device_key = cli
+            .args
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("Usage: purge-discovery <device_key>"))?;
+        return purge_discovery(&discovery, &ha_client, &mut ha_eventloop, device_key).await;
+    }
 
-    let mut ha_options = MqttOptions::new(
-        &config.home_assistant.client_id,
-        &config.home_assistant.mqtt_host,
-        config.home_assistant.mqtt_port,
-    );
-    ha_options.set_credentials(
-        &config.home_assistant.mqtt_username,
-        &config.home_assistant.mqtt_password,
-    );
-    let (ha_client, mut ha_eventloop) =
-        AsyncClient::new(ha_options, config.limits.command_channel_size);
+    let device_aliases = config.device_aliases();
+    let mut accounts = Vec::new();
+    // A device shared with several accounts is bridged once, by the first account listing it.
+    // Device filters are applied later, so a reload can change them.
+    let mut account_device_ids = std::collections::HashSet::new();
+    for account_config in config.olarm.accounts() {
+        // Let's get our JWT access token first
+        let olarm_client = Arc::new(CachedOlarmClient::new(OlarmClient::new(
+            account_config.api_token.clone(),
+            &account_config.username,
+            &account_config.password,
+        )));
+
+        let login_response = olarm_client.get_oauth_response().await?; //todo: retries
+
+        let user_devices = olarm_client
+            .get_user(&login_response.user_index.to_string())
+            .await?;
+
+        let mut devices = Vec::new();
+        for dev in user_devices.devices {
+            if !account_device_ids.insert(dev.id.clone()) {
+                warn!(
+                    "Device {} ({}) is already bridged by another account, skipping",
+                    dev.name, dev.id
+                );
+            } else {
+                devices.push(dev);
+            }
+        }
+
+        let account = Account {
+            name: account_config.name.clone(),
+            api_token: account_config.api_token.clone(),
+            olarm_client,
+            topics: Arc::new(
+                TopicBuilder::for_account(config.topics.clone(), &account_config.name)
+                    .with_aliases(device_aliases.clone()),
+            ),
+        };
+        accounts.push((account, devices));
+    }
 
     let published_discovery = Arc::new(DashSet::new());
 
     //Shared map: IMEI → command sender
     let senders: SenderMap = Arc::new(RwLock::new(HashMap::new()));
 
+    let bridge_state = Arc::new(BridgeState::new(discovery.clone()));
+
     // Run HA event loop in background
     let senders_router = senders.clone();
     let ha_published_discovery = published_discovery.clone();
+    let router_bridge_state = bridge_state.clone();
+    let router_topics = topics.clone();
+    let router_account_topics = accounts
+        .iter()
+        .map(|(account, _)| account.topics.clone())
+        .collect::<Vec<_>>();
+    let router_ha_client = ha_client.clone();
     tokio::spawn(async move {
         loop {
             match ha_eventloop.poll().await {
@@ -129,16 +219,50 @@ pub async fn main() -> anyhow::Result<()> {
                     if let Event::Incoming(Packet::Publish(p)) = event {
                         let payload = String::from_utf8_lossy(&p.payload).to_string();
 
-                        match command_topic_parser(&p.topic, &payload) {
+                        if p.topic == router_topics.bridge("restart/set") {
+                            router_bridge_state.restart_all();
+                            continue;
+                        }
+
+                        let parsed = router_account_topics.iter().find_map(|account_topics| {
+                            command_topic_parser(account_topics, &p.topic, &payload)
+                                .map(|parse_result| (account_topics, parse_result))
+                        });
+                        match parsed {
                             None => {
                                 warn!("Failed to parse topic: {:?}", p.topic);
                             }
-                            Some(parse_result) => {
-                                match senders_router.read().await.get(&parse_result.device_id) {
+                            // Handled here so devices the bridge no longer serves can be purged too
+                            Some((
+                                account_topics,
+                                TopicParseResult {
+                                    command: MqttCommand::PurgeDiscovery { device_id },
+                                    ..
+                                },
+                            )) => {
+                                let discovery = router_bridge_state.discovery.clone();
+                                let ha_client = router_ha_client.clone();
+                                let device_key = account_topics.device_key(&device_id);
+                                tokio::spawn(async move {
+                                    match discovery.purge(&ha_client, &device_key).await {
+                                        Ok(count) => info!(
+                                            "Purged {} discovery configs for device {}",
+                                            count, device_key
+                                        ),
+                                        Err(e) => error!(
+                                            "Failed to purge discovery for device {}: {:?}",
+                                            device_key, e
+                                        ),
+                                    }
+                                });
+                            }
+                            Some((account_topics, parse_result)) => {
+                                let device_key = account_topics.device_key(&parse_result.device_id);
+                                match senders_router.read().await.get(&device_key) {
                                     None => {
                                         error!(
-                                            "Received command for device_id {} that is not in the senders map",
-                                            parse_result.device_id
+                                            "Received command for device {} that is not in the senders map",
+                                            device_key
                                         );
                                     }
                                     Some(sender) => {
@@ -162,46 +286,312 @@ pub async fn main() -> anyhow::Result<()> {
         }
     });
 
-    for dev in user_devices.devices {
-        let local_olarm_client = olarm_client.clone();
-        let local_ha_client = ha_client.clone();
-        let local_senders = senders.clone();
-        let device_id = dev.id.clone();
-        let local_config = config.clone();
-        let local_published_discovery = published_discovery.clone();
-
-        tokio::spawn(async move {
-            loop {
-                let (tx, rx) =
-                    mpsc::channel::<MqttCommand>(local_config.limits.command_channel_size);
-                local_senders.write().await.insert(device_id.clone(), tx);
-                if let Err(e) = run_alarm_client(
-                    &local_config.olarm.broker_url,
-                    local_config.olarm.broker_port,
+    let bridge_processor = BridgeProcessor {
+        ha_client: ha_client.clone(),
+        accounts: accounts.iter().map(|(account, _)| account.clone()).collect(),
+        senders: senders.clone(),
+        bridge_state: bridge_state.clone(),
+        published_discovery: published_discovery.clone(),
+        topics: topics.clone(),
+    };
+    let bridge_interval = Duration::from_secs(config.intervals.status_tick_seconds);
+    tokio::spawn(async move { bridge_processor.run(bridge_interval).await });
+
+    let (config_tx, config_rx) = watch::channel(Arc::new(config));
+    let config_watcher = ConfigWatcher {
+        path: cli.config_path.clone(),
+        config_tx,
+        console_filter: console_filter_handle,
+    };
+    tokio::spawn(config_watcher.run());
+
+    let devices = accounts
+        .into_iter()
+        .flat_map(|(account, devices)| devices.into_iter().map(move |dev| (account.clone(), dev)))
+        .collect();
+    // Runs for as long as the bridge does
+    supervise_devices(
+        devices,
+        config_rx,
+        ha_client,
+        senders,
+        published_discovery,
+        bridge_state,
+    )
+    .await;
+    Ok(())
+}
+
+/// Runs a connection task for every bridged device, starting and stopping tasks as the
+/// device filters are changed by config reloads
+async fn supervise_devices(
+    devices: Vec<(Account<CachedOlarmClient<OlarmClient>>, UserDevice)>,
+    mut config_rx: watch::Receiver<Arc<Config>>,
+    ha_client: AsyncClient,
+    senders: SenderMap,
+    published_discovery: Arc<DashSet<String>>,
+    bridge_state: Arc<BridgeState>,
+) {
+    let mut tasks: HashMap<String, JoinHandle<()>> = HashMap::new();
+    loop {
+        let config = config_rx.borrow_and_update().clone();
+        for (account, dev) in &devices {
+            let device_key = account.topics.device_key(&dev.id);
+            let bridged = config.bridges_device(&account.name, dev);
+            if bridged && !tasks.contains_key(&device_key) {
+                info!("Bridging device {} ({})", dev.name, device_key);
+                let task = tokio::spawn(run_device(
+                    account.clone(),
                     dev.clone(),
-                    local_olarm_client.clone(),
-                    local_ha_client.clone(),
-                    rx,
-                    &local_config,
-                    local_published_discovery.clone(),
-                )
-                .await
+                    config_rx.clone(),
+                    ha_client.clone(),
+                    senders.clone(),
+                    published_discovery.clone(),
+                    bridge_state.clone(),
+                ));
+                tasks.insert(device_key, task);
+            } else if !bridged && let Some(task) = tasks.remove(&device_key) {
+                info!("Device {} ({}) is no longer bridged, stopping it", dev.name, device_key);
+                task.abort();
+                senders.write().await.remove(&device_key);
+                bridge_state.connected_devices.remove(&device_key);
+                if let Err(e) = ha_client
+                    .publish(
+                        account.topics.device_availability(&dev.id),
+                        QoS::AtLeastOnce,
+                        true,
+                        AvailabilityState::Offline.as_serde_value(),
+                    )
+                    .await
                 {
-                    error!("Alarm client {} failed: {:?}", dev.id, e);
-                    tokio::time::sleep(Duration::from_secs(
-                        local_config.intervals.reconnect_delay_seconds,
-                    ))
-                    .await;
+                    error!("Failed to mark device {} offline: {:?}", device_key, e);
                 }
+            } else if !bridged {
+                debug!("Skipping device {} ({}) excluded by config", dev.name, device_key);
             }
-        });
+        }
+        if config_rx.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Keeps a device connected, reconnecting after failures and when its config changes
+async fn run_device(
+    account: Account<CachedOlarmClient<OlarmClient>>,
+    dev: UserDevice,
+    mut config_rx: watch::Receiver<Arc<Config>>,
+    ha_client: AsyncClient,
+    senders: SenderMap,
+    published_discovery: Arc<DashSet<String>>,
+    bridge_state: Arc<BridgeState>,
+) {
+    let device_key = account.topics.device_key(&dev.id);
+    let mut restart_rx = bridge_state.restart_tx.subscribe();
+    loop {
+        let config = config_rx.borrow_and_update().clone();
+        // Naming templates may have changed since the account's topics were built
+        let device_account = Account {
+            topics: Arc::new(
+                TopicBuilder::for_account(config.topics.clone(), &account.name)
+                    .with_aliases(config.device_aliases()),
+            ),
+            ..account.clone()
+        };
+        let (tx, rx) = mpsc::channel::<MqttCommand>(config.limits.command_channel_size);
+        senders.write().await.insert(device_key.clone(), tx);
+        let result = tokio::select! {
+            result = run_alarm_client(
+                dev.clone(),
+                device_account,
+                ha_client.clone(),
+                rx,
+                &config,
+                published_discovery.clone(),
+                bridge_state.clone(),
+            ) => result,
+            _ = restart_rx.changed() => {
+                warn!("Restarting connection for device {}", device_key);
+                Ok(())
+            }
+            _ = device_config_changed(&mut config_rx, &config, &dev.id) => {
+                info!("Config of device {} changed, restarting its connection", device_key);
+                Ok(())
+            }
+        };
+        bridge_state.connected_devices.remove(&device_key);
+        if let Err(e) = result {
+            error!("Alarm client {} failed: {:?}", device_key, e);
+            tokio::time::sleep(Duration::from_secs(config.intervals.reconnect_delay_seconds))
+                .await;
+        }
     }
+}
 
-    // Prevent main from exiting
+/// Completes once a reload changes anything the device's connection was set up with
+async fn device_config_changed(
+    config_rx: &mut watch::Receiver<Arc<Config>>,
+    config: &Config,
+    device_id: &str,
+) {
     loop {
-        tokio::time::sleep(Duration::from_secs(60)).await;
+        if config_rx.changed().await.is_err() {
+            // The config watcher is gone, so nothing will change
+            return std::future::pending().await;
+        }
+        if config.affects_device(&config_rx.borrow(), device_id) {
+            return;
+        }
     }
 }
+/// Connection options for the HA broker
+fn ha_mqtt_options(config: &Config, client_id: &str) -> anyhow::Result<MqttOptions> {
+    let ha = &config.home_assistant;
+    // rumqttc takes a websocket's host and port from the URL
+    let host = match ha.transport {
+        BrokerTransport::Ws => format!("ws://{}:{}{}", ha.mqtt_host, ha.mqtt_port, ha.ws_path),
+        BrokerTransport::Wss => format!("wss://{}:{}{}", ha.mqtt_host, ha.mqtt_port, ha.ws_path),
+        BrokerTransport::Tcp | BrokerTransport::Tls => ha.mqtt_host.clone(),
+    };
+    let mut ha_options = MqttOptions::new(client_id, host, ha.mqtt_port);
+    ha_options.set_credentials(&ha.mqtt_username, &ha.mqtt_password);
+    ha_options.set_keep_alive(Duration::from_secs(ha.keep_alive_seconds));
+    ha_options.set_clean_session(ha.clean_session);
+    let transport = match ha.transport {
+        BrokerTransport::Tcp => Transport::Tcp,
+        BrokerTransport::Tls => Transport::Tls(ha_tls_configuration(ha)?),
+        BrokerTransport::Ws => Transport::Ws,
+        BrokerTransport::Wss => Transport::Wss(ha_tls_configuration(ha)?),
+    };
+    ha_options.set_transport(transport);
+    Ok(ha_options)
+}
+
+/// The system roots, unless a CA bundle is configured
+fn ha_tls_configuration(ha: &HomeAssistantConfig) -> anyhow::Result<TlsConfiguration> {
+    let read = |path: &str| {
+        std::fs::read(path).with_context(|| format!("Unable to read {}", path))
+    };
+    let Some(ca_file) = &ha.ca_file else {
+        return Ok(TlsConfiguration::default());
+    };
+    let client_auth = match (&ha.client_cert_file, &ha.client_key_file) {
+        (Some(cert_file), Some(key_file)) => Some((read(cert_file)?, read(key_file)?)),
+        _ => None,
+    };
+    Ok(TlsConfiguration::Simple {
+        ca: read(ca_file)?,
+        alpn: (!ha.alpn.is_empty())
+            .then(|| ha.alpn.iter().map(|protocol| protocol.as_bytes().to_vec()).collect()),
+        client_auth,
+    })
+}
+
+/// `check-config [--connect]`: validates the config and, with `--connect`, logs into every
+/// Olarm account and connects to the HA broker. Fails if anything is wrong.
+async fn check_config(cli: &Cli) -> anyhow::Result<()> {
+    let config = Config::load(&cli.config_path)?;
+    println!("{} is valid", cli.config_path);
+    if !cli.args.iter().any(|arg| arg == "--connect") {
+        return Ok(());
+    }
+
+    let mut failed = false;
+    for account in config.olarm.accounts() {
+        let name = if account.name.is_empty() { "default" } else { &account.name };
+        let olarm_client = OlarmClient::new(account.api_token.clone(), &account.username, &account.password);
+        let result = async {
+            let login_response = olarm_client.get_oauth_response().await?;
+            olarm_client.get_user(&login_response.user_index.to_string()).await
+        }
+        .await;
+        match result {
+            Ok(user) => {
+                let bridged = user
+                    .devices
+                    .iter()
+                    .filter(|dev| config.olarm.devices.allows(dev) && account.devices.allows(dev))
+                    .count();
+                println!(
+                    "Olarm account {}: logged in, {} devices, {} bridged",
+                    name,
+                    user.devices.len(),
+                    bridged
+                );
+            }
+            Err(e) => {
+                println!("Olarm account {}: login failed: {:#}", name, e);
+                failed = true;
+            }
+        }
+    }
+
+    // A separate client id so a running bridge isn't disconnected
+    let client_id = format!("{}-check", config.home_assistant.client_id);
+    let (_ha_client, mut ha_eventloop) = AsyncClient::new(ha_mqtt_options(&config, &client_id)?, 10);
+    let connected = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            match ha_eventloop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => return Ok(()),
+                Ok(_) => {}
+                Err(e) => return Err(anyhow::Error::from(e)),
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out after 10s")));
+    match connected {
+        Ok(()) => println!(
+            "HA broker {}:{}: connected",
+            config.home_assistant.mqtt_host, config.home_assistant.mqtt_port
+        ),
+        Err(e) => {
+            println!(
+                "HA broker {}:{}: connection failed: {:#}",
+                config.home_assistant.mqtt_host, config.home_assistant.mqtt_port, e
+            );
+            failed = true;
+        }
+    }
+
+    if failed {
+        anyhow::bail!("Connection checks failed");
+    }
+    Ok(())
+}
+
+/// `purge-discovery <device_key>`: removes every discovery config recorded for the device and exits.
+/// The key is the device id, prefixed with `<account>_` for devices of a named account.
+async fn purge_discovery(
+    discovery: &DiscoveryRegistry,
+    ha_client: &AsyncClient,
+    ha_eventloop: &mut EventLoop,
+    device_key: &str,
+) -> anyhow::Result<()> {
+    let mut acked = 0;
+    let poll = async {
+        loop {
+            if let Event::Incoming(Packet::PubAck(_)) = ha_eventloop.poll().await? {
+                acked += 1;
+            }
+        }
+    };
+    let purged = tokio::select! {
+        purged = discovery.purge(ha_client, device_key) => purged?,
+        result = poll => return result,
+    };
+
+    // Wait until the broker has the empty retained payloads before exiting
+    while acked < purged {
+        let event = tokio::time::timeout(Duration::from_secs(10), ha_eventloop.poll()).await??;
+        if let Event::Incoming(Packet::PubAck(_)) = event {
+            acked += 1;
+        }
+    }
+    println!("Purged {} discovery configs for device {}", purged, device_key);
+    Ok(())
+}
+
 async fn get_device_profile<T>(olarm_client: &T, device_id: &str) -> DeviceProfile
 where
     T: OlarmApiTrait,
@@ -213,16 +603,13 @@ where
                 "Error occurred while getting device, using fallback. {:?}",
                 e
             );
-            let user_id;
-            match olarm_client.get_oauth_response().await {
-                Ok(response) => {
-                    user_id = response.user_index;
-                }
+            let user_id = match olarm_client.get_oauth_response().await {
+                Ok(response) => response.user_index,
                 Err(e) => {
                     error!("{:?}", e);
                     std::process::exit(1);
                 }
-            }
+            };
 
             let devices = olarm_client
                 .get_user(&user_id.to_string())
@@ -240,29 +627,45 @@ where
     }
 }
 
+/// An Olarm account's API client and the topic namespace of its devices
+#[derive(Clone)]
+pub struct Account<T> {
+    /// Empty for the single legacy account
+    pub name: String,
+    pub api_token: String,
+    pub olarm_client: Arc<T>,
+    pub topics: Arc<TopicBuilder>,
+}
+
 async fn run_alarm_client<T>(
-    host: &str,
-    port: u16,
     device: UserDevice,
-    olarm_client: Arc<T>,
+    account: Account<T>,
     ha_client: AsyncClient,
     mut rx: Receiver<MqttCommand>,
     config: &Config,
     published_discovery: Arc<DashSet<String>>,
+    bridge_state: Arc<BridgeState>,
 ) -> anyhow::Result<()>
 where
     T: OlarmApiTrait + Clone + Send + Sync + 'static,
 {
     let imei = device.imei.clone();
     let client_id = format!("native-app-oauth-{}", imei);
-    let mqtt_password = &config.olarm.api_token; //olarm_client.get_oauth_response().await?.oat;
+    let mqtt_password = &account.api_token; //olarm_client.get_oauth_response().await?.oat;
     let device_state_topic = format!("so/app/v1/{}", imei);
     let status_topic = format!("si/app/v2/{}/status", imei);
-    let ha_availability_topic = format!("olarm/device/{}/availability", &device.id);
+    let olarm_client = account.olarm_client.clone();
+    let topics = account.topics.clone();
+    let device_key = topics.device_key(&device.id);
+    let ha_availability_topic = topics.device_availability(&device.id);
     const MQTT_USERNAME: &str = "native_app";
 
     // --- Per-device MQTT connection ---
-    let mut mqttoptions = MqttOptions::new(client_id, host, port);
+    let mut mqttoptions = MqttOptions::new(
+        client_id,
+        &config.olarm.broker_url,
+        config.olarm.broker_port,
+    );
     mqttoptions.set_clean_session(true);
     mqttoptions.set_credentials(MQTT_USERNAME, mqtt_password);
     mqttoptions.set_keep_alive(Duration::from_secs(
@@ -278,6 +681,7 @@ where
     client
         .subscribe(&device_state_topic, QoS::AtLeastOnce)
         .await?;
+    bridge_state.connected_devices.insert(device_key.clone());
 
     // Publish status requests on the same task to avoid leaked background publishers on reconnect
     let mut status_tick =
@@ -295,20 +699,60 @@ where
     // }
     let device_profile = get_device_profile(&*olarm_client, &device.id).await;
 
+    // Republish all of this device's discovery on the first status, and remove whatever
+    // was published before but is not republished (e.g. zones that no longer exist)
+    let discovery = bridge_state.discovery.clone();
+    discovery.begin_sweep(&device_key);
+    published_discovery.retain(|unique_id| !TopicBuilder::is_device_unique_id(&device_key, unique_id));
+
+    // Dropped with this function's future, which ends the tasks spawned for the connection
+    let (_session, session) = watch::channel(());
+
     // Track which discovery configs we've already published
     let processor_state = Arc::new(RwLock::new(ProcessorState {
         device_profile: device_profile.clone(),
         published_discovery: published_discovery.clone(),
         device: device.clone(),
+        device_config: config.device_config(&device.id),
+        latest_response: None,
+        previous_area_states: HashMap::new(),
+        previous_zone_states: HashMap::new(),
+        zone_debounce: HashMap::new(),
+        area_countdowns: HashMap::new(),
+        zone_types: config.zone_types.clone(),
+        session,
     }));
 
     let ha_processor = HaProcessor {
         mqtt_olarm_client: client.clone(),
         http_olarm_client: olarm_client.clone(),
         processor_state: processor_state.clone(),
+        ha_client: ha_client.clone(),
+        topics: topics.clone(),
     };
     let zone_processor = ZonesProcessor {
         ha_client: ha_client.clone(),
+        topics: topics.clone(),
+        discovery: discovery.clone(),
+        trip_counters: Arc::new(TripCounters::load(
+            config.storage.trip_counters_path(&device_key),
+        )),
+    };
+    let panel_processor = PanelProcessor {
+        ha_client: ha_client.clone(),
+        olarm_client: olarm_client.clone(),
+        topics: topics.clone(),
+        discovery: discovery.clone(),
+    };
+    let outputs_processor = OutputsProcessor {
+        ha_client: ha_client.clone(),
+        topics: topics.clone(),
+        discovery: discovery.clone(),
+    };
+    let triggers_processor = TriggersProcessor {
+        ha_client: ha_client.clone(),
+        topics: topics.clone(),
+        discovery: discovery.clone(),
     };
 
     tokio::spawn(async move {
@@ -322,9 +766,30 @@ where
     let local_client = client.clone();
     let local_client2 = client.clone();
     let local_ha_client = ha_client.clone();
-    // Run both loops as futures and short-circuit on the first error
+    let ticker_zone_processor = zone_processor.clone();
+    let ticker_processor_state = processor_state.clone();
+    let ticker_device_key = device_key.clone();
+    let refresh_processor_state = processor_state.clone();
+    let refresh_zone_processor = zone_processor.clone();
+    let refresh_panel_processor = panel_processor.clone();
+    let refresh_outputs_processor = outputs_processor.clone();
+    let refresh_triggers_processor = triggers_processor.clone();
+    let refresh_discovery = discovery.clone();
+    let refresh_ha_client = ha_client.clone();
+    let refresh_olarm_client = olarm_client.clone();
+    let refresh_device_id = device.id.clone();
+    let refresh_device_key = device_key.clone();
+    // Held while a status is processed or a profile refresh republishes, so a refresh can't start
+    // a sweep while the reader is partway through one and remove what it just published
+    let sweep_lock = Arc::new(tokio::sync::Mutex::new(()));
+    let reader_sweep_lock = sweep_lock.clone();
+    let refresh_sweep_lock = sweep_lock;
+    let mut profile_tick =
+        tokio::time::interval(Duration::from_secs(config.intervals.profile_refresh_seconds));
+    // Run the loops as futures and short-circuit on the first error
     let reader = async move {
         let mut prev_message_hash: Option<u64> = None; // Store the hash of the previous message
+        let mut sweep_pending = true;
         loop {
             match event_loop.poll().await {
                 Ok(Event::Incoming(Packet::Publish(p))) => {
@@ -336,6 +801,8 @@ where
                     if let Some(prev_hash) = prev_message_hash
                         && prev_hash == current_hash
                     {
+                        // The device still answered, so release any command waiting on it
+                        local_client.notify_response().await;
                         continue; // Skip processing duplicate payload
                     }
                     prev_message_hash = Some(current_hash);
@@ -343,37 +810,24 @@ where
                     if let Ok(payload) = serde_json::from_str::<MqttDeviceResponse>(&payload_str) {
                         local_client.notify_response().await;
                         // debug!("{:?}", &payload);
+                        let _sweeping = reader_sweep_lock.lock().await;
+                        processor_state.write().await.latest_response = Some(payload.clone());
 
-                        // Process zones
-                        let local_processor_state = processor_state.clone();
-                        let local_zones_processor = zone_processor.clone();
-                        let payload_for_zones = payload.clone();
-                        let zones_handle = tokio::spawn(async move {
-                            if let Err(e) = local_zones_processor
-                                .handle(payload_for_zones, local_processor_state)
-                                .await
-                            {
-                                error!("Error occurred while processing zone data: {:?}", e);
-                            }
-                        });
-                        // Process panels
-                        let local_processor_state = processor_state.clone();
-                        let panel_processor = PanelProcessor {
-                            ha_client: ha_client.clone(),
-                            olarm_client: olarm_client.clone(),
-                        };
-                        let payload_for_panel = payload.clone();
-
-                        let panel_handle = tokio::spawn(async move {
-                            if let Err(e) = panel_processor
-                                .handle(payload_for_panel, local_processor_state)
-                                .await
-                            {
-                                error!("Error occurred while processing panel data: {:?}", e);
+                        let processed = process_status(
+                            payload,
+                            &processor_state,
+                            &zone_processor,
+                            &panel_processor,
+                            &outputs_processor,
+                            &triggers_processor,
+                        )
+                        .await;
+                        if processed && sweep_pending {
+                            sweep_pending = false;
+                            if let Err(e) = discovery.finish_sweep(&ha_client, &device_key).await {
+                                error!("Failed to remove stale discovery configs: {:?}", e);
                             }
-                        });
-
-                        let _ = join!(zones_handle, panel_handle);
+                        }
                     } else if serde_json::from_str::<MqttWifiResponse>(&payload_str).is_ok() {
                         // wifi status message, intentionally ignored
                     } else {
@@ -383,7 +837,11 @@ where
                         )
                     }
                 }
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    local_client.set_connected(true);
+                }
                 Err(e) => {
+                    local_client.set_connected(false);
                     // Bubble up to trigger restart
                     error!("MQTT event loop error: {:?}", e);
                     return Err::<(), anyhow::Error>(anyhow::Error::from(e));
@@ -396,8 +854,20 @@ where
     };
 
     let ticker = async move {
+        let mut trip_day = Local::now().date_naive();
         loop {
             let _ = status_tick.tick().await;
+            // Trips today only change on a trip otherwise, so reset them here after midnight
+            let today = Local::now().date_naive();
+            if today != trip_day {
+                trip_day = today;
+                if let Err(e) = ticker_zone_processor
+                    .publish_trips_today(&ticker_processor_state)
+                    .await
+                {
+                    error!("Failed to reset trips today of {}: {:?}", ticker_device_key, e);
+                }
+            }
             // If either publish fails, return the error to trigger restart
             if let Err(e) = local_ha_client
                 .publish(&ha_availability_topic, QoS::AtLeastOnce, true, "online")
@@ -427,8 +897,151 @@ where
         }
     };
 
-    // If either future returns Err, this returns Err immediately.
-    tokio::try_join!(reader, ticker).map(|_: (_, _)| ())
+    let profile_refresher = async move {
+        // The first tick completes immediately and the profile was only just fetched
+        profile_tick.tick().await;
+        loop {
+            profile_tick.tick().await;
+            let (device_profile, device_triggers) =
+                match refresh_olarm_client.get_device(&refresh_device_id).await {
+                    Ok(response) => (response.device_profile, response.device_triggers),
+                    Err(e) => {
+                        warn!("Failed to refresh profile of {}: {:?}", refresh_device_id, e);
+                        continue;
+                    }
+                };
+
+            let (profile_changed, triggers_changed) = {
+                let mut state = refresh_processor_state.write().await;
+                let triggers_changed = state.device.triggers != device_triggers;
+                state.device.triggers = device_triggers;
+                (state.device_profile.entities_differ(&device_profile), triggers_changed)
+            };
+            if !profile_changed {
+                if triggers_changed
+                    && let Err(e) = refresh_triggers_processor
+                        .publish(&refresh_processor_state)
+                        .await
+                {
+                    error!("Failed to publish triggers of {}: {:?}", refresh_device_id, e);
+                }
+                continue;
+            }
+
+            let _sweeping = refresh_sweep_lock.lock().await;
+            let latest_response = {
+                let mut state = refresh_processor_state.write().await;
+                info!("Profile of {} changed, republishing discovery", refresh_device_id);
+                state.device_profile = device_profile;
+                state.latest_response.clone()
+            };
+            // Without a status the reader's initial sweep republishes with the new profile
+            let Some(latest_response) = latest_response else {
+                continue;
+            };
+
+            refresh_discovery.begin_sweep(&refresh_device_key);
+            published_discovery
+                .retain(|unique_id| !TopicBuilder::is_device_unique_id(&refresh_device_key, unique_id));
+            if process_status(
+                latest_response,
+                &refresh_processor_state,
+                &refresh_zone_processor,
+                &refresh_panel_processor,
+                &refresh_outputs_processor,
+                &refresh_triggers_processor,
+            )
+            .await
+                && let Err(e) = refresh_discovery
+                    .finish_sweep(&refresh_ha_client, &refresh_device_key)
+                    .await
+            {
+                error!("Failed to remove stale discovery configs: {:?}", e);
+            }
+        }
+    };
+
+    // If either loop returns Err, this returns Err immediately.
+    // The profile refresher only logs its errors, so it never ends the connection.
+    tokio::select! {
+        result = async { tokio::try_join!(reader, ticker) } => result.map(|_: (_, _)| ()),
+        _ = profile_refresher => unreachable!("profile refresher never returns"),
+    }
+}
+
+/// Runs the zone, panel, output and trigger processors on a status. Returns whether all succeeded.
+async fn process_status<T>(
+    payload: MqttDeviceResponse,
+    processor_state: &Arc<RwLock<ProcessorState>>,
+    zones_processor: &ZonesProcessor,
+    panel_processor: &PanelProcessor<T>,
+    outputs_processor: &OutputsProcessor,
+    triggers_processor: &TriggersProcessor,
+) -> bool
+where
+    T: OlarmApiTrait + Clone + Send + Sync + 'static,
+{
+    // Process zones
+    let local_processor_state = processor_state.clone();
+    let local_zones_processor = zones_processor.clone();
+    let payload_for_zones = payload.clone();
+    let zones_handle = tokio::spawn(async move {
+        if let Err(e) = local_zones_processor
+            .handle(payload_for_zones, local_processor_state)
+            .await
+        {
+            error!("Error occurred while processing zone data: {:?}", e);
+            return false;
+        }
+        true
+    });
+    // Process panels
+    let local_processor_state = processor_state.clone();
+    let local_panel_processor = panel_processor.clone();
+    let payload_for_panel = payload.clone();
+    let panel_handle = tokio::spawn(async move {
+        if let Err(e) = local_panel_processor
+            .handle(payload_for_panel, local_processor_state)
+            .await
+        {
+            error!("Error occurred while processing panel data: {:?}", e);
+            return false;
+        }
+        true
+    });
+    // Process on-board PGMs and doors
+    let local_processor_state = processor_state.clone();
+    let local_outputs_processor = outputs_processor.clone();
+    let payload_for_outputs = payload.clone();
+    let outputs_handle = tokio::spawn(async move {
+        if let Err(e) = local_outputs_processor
+            .handle(payload_for_outputs, local_processor_state)
+            .await
+        {
+            error!("Error occurred while processing output data: {:?}", e);
+            return false;
+        }
+        true
+    });
+
+    // Process notification triggers
+    let local_processor_state = processor_state.clone();
+    let local_triggers_processor = triggers_processor.clone();
+    let triggers_handle = tokio::spawn(async move {
+        if let Err(e) = local_triggers_processor
+            .handle(payload, local_processor_state)
+            .await
+        {
+            error!("Error occurred while processing trigger data: {:?}", e);
+            return false;
+        }
+        true
+    });
+
+    matches!(
+        join!(zones_handle, panel_handle, outputs_handle, triggers_handle),
+        (Ok(true), Ok(true), Ok(true), Ok(true))
+    )
 }
 #[derive(Debug, Clone)]
 pub struct ZoneObject {
@@ -439,12 +1052,57 @@ pub struct ZoneObject {
     pub zone_number: usize,
     pub attributes: Option<HashMap<String, String>>,
     pub bypass_state: String,
+    pub icon: Option<String>,
+    pub off_delay: Option<u64>,
+    /// Debounce: minimum seconds a published on/off state is held
+    pub min_on_seconds: u64,
+    pub min_off_seconds: u64,
+    /// Configured to have no HA entities
+    pub hidden: bool,
+    pub status: ZoneStatus,
+    /// Area the zone is attached to, from the config or auto-detected on single-area panels
+    pub area: Option<ZoneArea>,
+}
+
+impl ZoneObject {
+    /// The HA device the zone's entities belong to
+    pub fn ha_device(&self, device: &UserDevice, topics: &TopicBuilder) -> Device {
+        match &self.area {
+            Some(area) if area.own_device => Device::for_area(device, area.number, &area.label, topics),
+            _ => Device::from_user_device(device, topics),
+        }
+    }
+}
+
+/// The area a zone belongs to
+#[derive(Debug, Clone)]
+pub struct ZoneArea {
+    pub number: usize,
+    pub label: String,
+    /// Whether the area has its own HA device, which is the case on panels with several areas
+    pub own_device: bool,
 }
 
 pub struct AreaObject {
     pub name: String,
     pub state: Option<String>,
+    pub raw_state: String,
+    pub detail: Option<String>,
+    pub last_changed: Option<DateTime<Utc>>,
     pub area_number: usize,
+    /// Whether the area has its own HA device, which is the case on panels with several areas
+    pub own_device: bool,
+}
+
+impl AreaObject {
+    /// The HA device the area's entities belong to
+    pub fn ha_device(&self, device: &UserDevice, topics: &TopicBuilder) -> Device {
+        if self.own_device {
+            Device::for_area(device, self.area_number, &self.name, topics)
+        } else {
+            Device::from_user_device(device, topics)
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -458,17 +1116,40 @@ pub enum MqttCommand {
         device_id: String,
         zone_number: usize,
         payload: ZoneBypassRequest,
+    },
+    ArmWithBypass {
+        device_id: String,
+        area_number: usize,
+        payload: ArmBypassRequest,
+    },
+    SetPgmOb {
+        device_id: String,
+        pgm_number: usize,
         action_cmd: ActionCmd,
     },
+    SetDoor {
+        device_id: String,
+        door_number: usize,
+        action_cmd: ActionCmd,
+    },
+    /// Remove every discovery config the bridge published for the device
+    PurgeDiscovery { device_id: String },
 }
 
 pub struct TopicParseResult {
     pub device_id: String,
     pub command: MqttCommand,
 }
-pub fn command_topic_parser(topic: &str, payload: &str) -> Option<TopicParseResult> {
-    let mut parts: Vec<&str> = topic.split('/').collect();
-    parts.resize(10, "");
+pub fn command_topic_parser(
+    topics: &TopicBuilder,
+    topic: &str,
+    payload: &str,
+) -> Option<TopicParseResult> {
+    let mut parts = topics.strip_base(topic)?;
+    parts.resize(8, "");
+    if parts[0] == "device" {
+        parts[1] = topics.device_id_of(parts[1]);
+    }
 
     if payload.is_empty() {
         error!("Empty payload for topic: {:?}", topic);
@@ -477,10 +1158,22 @@ pub fn command_topic_parser(topic: &str, payload: &str) -> Option<TopicParseResu
     }
 
     match (
-        parts[0], parts[1], parts[2], parts[3], parts[4], parts[5], parts[6], parts[7], parts[8],
-        parts[9],
+        parts[0], parts[1], parts[2], parts[3], parts[4], parts[5], parts[6], parts[7],
     ) {
-        ("olarm", "device", device_id, "area", area_num, "set", ..) => {
+        ("device", device_id, "area", area_num, "set", ..)
+            if payload == ARM_CUSTOM_BYPASS_PAYLOAD =>
+        {
+            let area_number = area_num.parse::<usize>().ok()?;
+            Some(TopicParseResult {
+                device_id: device_id.to_string(),
+                command: MqttCommand::ArmWithBypass {
+                    device_id: device_id.to_string(),
+                    area_number,
+                    payload: ArmBypassRequest::new(ActionCmd::AreaArm),
+                },
+            })
+        }
+        ("device", device_id, "area", area_num, "set", ..) => {
             if let Ok(command) = serde_json::from_str::<ActionCmd>(payload) {
                 let area_number = area_num.parse::<usize>().ok()?;
                 Some(TopicParseResult {
@@ -499,8 +1192,28 @@ pub fn command_topic_parser(topic: &str, payload: &str) -> Option<TopicParseResu
                 None
             }
         }
-        //olarm/device/UUID/zone/2/bypass/set
-        ("olarm", "device", device_id, "zone", zone_num, "bypass", "set", ..) => {
+        //<base>/device/UUID/area/1/arm_bypass/set
+        ("device", device_id, "area", area_num, "arm_bypass", "set", ..) => {
+            if let Ok(request) = serde_json::from_str::<ArmBypassRequest>(payload) {
+                let area_number = area_num.parse::<usize>().ok()?;
+                Some(TopicParseResult {
+                    device_id: device_id.to_string(),
+                    command: MqttCommand::ArmWithBypass {
+                        device_id: device_id.to_string(),
+                        area_number,
+                        payload: request,
+                    },
+                })
+            } else {
+                error!(
+                    "Unable to deserialize payload: {:?} for topic: {:?}",
+                    payload, topic
+                );
+                None
+            }
+        }
+        //<base>/device/UUID/zone/2/bypass/set
+        ("device", device_id, "zone", zone_num, "bypass", "set", ..) => {
             if let Ok(request) = serde_json::from_str::<ZoneBypassRequest>(payload) {
                 let zone_number = zone_num.parse::<usize>().ok()?;
                 Some(TopicParseResult {
@@ -509,7 +1222,6 @@ pub fn command_topic_parser(topic: &str, payload: &str) -> Option<TopicParseResu
                         device_id: device_id.to_string(),
                         zone_number,
                         payload: request,
-                        action_cmd: ActionCmd::ZoneBypass,
                     },
                 })
             } else {
@@ -520,8 +1232,135 @@ pub fn command_topic_parser(topic: &str, payload: &str) -> Option<TopicParseResu
                 None
             }
         }
+        //<base>/device/UUID/pgm_ob/1/set
+        ("device", device_id, "pgm_ob", pgm_num, "set", ..) => {
+            match serde_json::from_str::<ActionCmd>(payload) {
+                Ok(command) if command.is_pgm_ob() => Some(TopicParseResult {
+                    device_id: device_id.to_string(),
+                    command: MqttCommand::SetPgmOb {
+                        device_id: device_id.to_string(),
+                        pgm_number: pgm_num.parse::<usize>().ok()?,
+                        action_cmd: command,
+                    },
+                }),
+                _ => {
+                    error!(
+                        "Unable to deserialize payload: {:?} for topic: {:?}",
+                        payload, topic
+                    );
+                    None
+                }
+            }
+        }
+        //<base>/device/UUID/door/1/set
+        ("device", device_id, "door", door_num, "set", ..) => {
+            match serde_json::from_str::<ActionCmd>(payload) {
+                Ok(command @ ActionCmd::DoorUnlock) => Some(TopicParseResult {
+                    device_id: device_id.to_string(),
+                    command: MqttCommand::SetDoor {
+                        device_id: device_id.to_string(),
+                        door_number: door_num.parse::<usize>().ok()?,
+                        action_cmd: command,
+                    },
+                }),
+                _ => {
+                    error!(
+                        "Unable to deserialize payload: {:?} for topic: {:?}",
+                        payload, topic
+                    );
+                    None
+                }
+            }
+        }
+        //<base>/device/UUID/discovery/purge
+        ("device", device_id, "discovery", "purge", ..) => Some(TopicParseResult {
+            device_id: device_id.to_string(),
+            command: MqttCommand::PurgeDiscovery {
+                device_id: device_id.to_string(),
+            },
+        }),
         _ => None,
     }
 }
 
 type SenderMap = Arc<RwLock<HashMap<String, mpsc::Sender<MqttCommand>>>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TopicsConfig;
+
+    fn parse(topic: &str, payload: &str) -> Option<TopicParseResult> {
+        command_topic_parser(&TopicBuilder::new(TopicsConfig::default()), topic, payload)
+    }
+
+    #[test]
+    fn parses_area_commands() {
+        let result = parse("olarm/device/abc/area/2/set", r#""area-stay""#).unwrap();
+        assert_eq!(result.device_id, "abc");
+        assert!(matches!(
+            result.command,
+            MqttCommand::SetArea { area_number: 2, action_cmd: ActionCmd::AreaStay, .. }
+        ));
+    }
+
+    #[test]
+    fn parses_custom_bypass_as_arm_with_bypass() {
+        let result = parse("olarm/device/abc/area/1/set", ARM_CUSTOM_BYPASS_PAYLOAD).unwrap();
+        assert!(matches!(
+            result.command,
+            MqttCommand::ArmWithBypass { area_number: 1, payload: ArmBypassRequest { action_cmd: ActionCmd::AreaArm }, .. }
+        ));
+
+        let result = parse("olarm/device/abc/area/1/arm_bypass/set", r#"{"action_cmd":"area-sleep"}"#).unwrap();
+        assert!(matches!(
+            result.command,
+            MqttCommand::ArmWithBypass { area_number: 1, payload: ArmBypassRequest { action_cmd: ActionCmd::AreaSleep }, .. }
+        ));
+    }
+
+    #[test]
+    fn parses_zone_bypass() {
+        let result = parse("olarm/device/abc/zone/7/bypass/set", r#"{"bypass":false}"#).unwrap();
+        assert!(matches!(
+            result.command,
+            MqttCommand::SetZoneBypass { zone_number: 7, payload: ZoneBypassRequest { bypass: false }, .. }
+        ));
+    }
+
+    #[test]
+    fn parses_output_commands() {
+        let result = parse("olarm/device/abc/pgm_ob/2/set", r#""pgmob-pulse""#).unwrap();
+        assert!(matches!(
+            result.command,
+            MqttCommand::SetPgmOb { pgm_number: 2, action_cmd: ActionCmd::PgmObPulse, .. }
+        ));
+        let result = parse("olarm/device/abc/door/1/set", r#""door-unlock""#).unwrap();
+        assert!(matches!(
+            result.command,
+            MqttCommand::SetDoor { door_number: 1, action_cmd: ActionCmd::DoorUnlock, .. }
+        ));
+        // Only the commands of that kind of output
+        assert!(parse("olarm/device/abc/pgm_ob/2/set", r#""area-disarm""#).is_none());
+        assert!(parse("olarm/device/abc/door/1/set", r#""pgmob-open""#).is_none());
+    }
+
+    #[test]
+    fn parses_discovery_purge() {
+        let result = parse("olarm/device/abc/discovery/purge", "purge").unwrap();
+        assert!(matches!(result.command, MqttCommand::PurgeDiscovery { device_id } if device_id == "abc"));
+    }
+
+    #[test]
+    fn rejects_unusable_commands() {
+        // Empty or unparseable payloads
+        assert!(parse("olarm/device/abc/area/1/set", "").is_none());
+        assert!(parse("olarm/device/abc/area/1/set", "arm").is_none());
+        assert!(parse("olarm/device/abc/zone/7/bypass/set", "on").is_none());
+        // Numbers that aren't numbers
+        assert!(parse("olarm/device/abc/area/one/set", r#""area-arm""#).is_none());
+        // Topics the bridge doesn't own or handle
+        assert!(parse("other/device/abc/area/1/set", r#""area-arm""#).is_none());
+        assert!(parse("olarm/device/abc/area/1/state", r#""area-arm""#).is_none());
+    }
+}
@@ -1,14 +1,17 @@
 use crate::olarm_api::models::response::login_via_user_credentials_response::LoginViaUserCredentialsResponse;
 use crate::olarm_api::models::response::refresh_oauth_token_response::RefreshOAuthTokenResponse;
 use crate::olarm_api::models::response::{
-    device_response::DeviceResponse, devices_response::DevicesResponse,
+    device_events_response::DeviceEventsResponse, device_response::DeviceResponse, devices_response::DevicesResponse,
     get_actions_response::GetActionsResponse, user_response::UserResponse,
 };
 use crate::olarm_api::olarm_client::{OlarmApiTrait, OlarmClient};
 use anyhow::Result;
 use moka::future::Cache;
 use reqwest::Response;
+use std::sync::Arc;
 use std::time::Duration;
+use chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
 use crate::olarm_api::models::request::actions_request::ActionsRequest;
 
 #[derive(Clone)]
@@ -20,56 +23,81 @@ where
     devices_cache: Cache<&'static str, DevicesResponse>,
     device_cache: Cache<String, DeviceResponse>,
     actions_cache: Cache<String, GetActionsResponse>,
-    user_cache: Cache<String, UserResponse>
+    user_cache: Cache<String, UserResponse>,
+    last_error: Arc<RwLock<Option<ApiError>>>,
+}
+
+/// A failed Olarm API call
+#[derive(Clone, Debug)]
+pub struct ApiError {
+    pub occurred_at: DateTime<Utc>,
+    pub message: String,
 }
 impl OlarmApiTrait for CachedOlarmClient<OlarmClient> {
         async fn get_user(&self, user_id: &str) -> Result<UserResponse> {
         let key = user_id.to_string();
 
-        self.user_cache
+        let result = self.user_cache
             .try_get_with(key.clone(), async move { self.client.get_user(&key).await })
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to fetch user: {}", e))
+            .map_err(|e| anyhow::anyhow!("Failed to fetch user: {}", e));
+        self.record(result).await
     }
 
     async fn get_devices(&self) -> Result<DevicesResponse> {
-        self.devices_cache
+        let result = self.devices_cache
             .try_get_with("devices", async { self.client.get_devices().await })
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to fetch devices: {}", e))
+            .map_err(|e| anyhow::anyhow!("Failed to fetch devices: {}", e));
+        self.record(result).await
     }
 
     async fn get_device(&self, device_id: &str) -> Result<DeviceResponse> {
         let key = device_id.to_string();
 
-        self.device_cache
+        let result = self.device_cache
             .try_get_with(
                 key.clone(),
                 async move { self.client.get_device(&key).await },
             )
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to fetch device: {}", e))
+            .map_err(|e| anyhow::anyhow!("Failed to fetch device: {}", e));
+        self.record(result).await
     }
 
     async fn send_action(&self, device_id: &str, payload: ActionsRequest) -> Result<Response> {
         // don't cache this, it's a POST request
-        self.client.send_action(device_id, payload).await
+        let result = self.client.send_action(device_id, payload).await;
+        if let Ok(response) = &result
+            && !response.status().is_success()
+        {
+            self.set_last_error(format!("Action failed with status {}", response.status())).await;
+        }
+        self.record(result).await
     }
 
     async fn get_actions(&self, device_id: &str) -> Result<GetActionsResponse> {
         let key = device_id.to_string();
 
-        self.actions_cache
+        let result = self.actions_cache
             .try_get_with(
                 key.clone(),
                 async move { self.client.get_actions(&key).await },
             )
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to fetch actions: {}", e))
+            .map_err(|e| anyhow::anyhow!("Failed to fetch actions: {}", e));
+        self.record(result).await
+    }
+
+    async fn get_events(&self, device_id: &str) -> Result<DeviceEventsResponse> {
+        // not cached, the event log is only read on demand
+        let result = self.client.get_events(device_id).await;
+        self.record(result).await
     }
 
     async fn get_oauth_response(&self) -> Result<LoginViaUserCredentialsResponse> {
-        self.client.get_oauth_response().await
+        let result = self.client.get_oauth_response().await;
+        self.record(result).await
     }
 
     async fn refresh_oauth_token(&self, refresh_token: &str) -> Result<RefreshOAuthTokenResponse> {
@@ -95,8 +123,27 @@ where
             user_cache: Cache::builder()
                 .time_to_live(Duration::from_secs(300))
                 .build(),
-           
+            last_error: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// The most recent failed Olarm API call
+    pub async fn last_error(&self) -> Option<ApiError> {
+        self.last_error.read().await.clone()
+    }
+
+    async fn set_last_error(&self, message: String) {
+        *self.last_error.write().await = Some(ApiError {
+            occurred_at: Utc::now(),
+            message,
+        });
+    }
+
+    async fn record<R>(&self, result: Result<R>) -> Result<R> {
+        if let Err(e) = &result {
+            self.set_last_error(format!("{:#}", e)).await;
         }
+        result
     }
 }
 
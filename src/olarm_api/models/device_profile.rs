@@ -36,3 +36,82 @@ pub struct DeviceProfile {
     pub doors_labels: Vec<String>,
     pub ver: i64,
 }
+
+impl DeviceProfile {
+    /// Whether the areas, zones, on-board PGMs or doors HA entities are built from differ between
+    /// the two profiles
+    pub fn entities_differ(&self, other: &DeviceProfile) -> bool {
+        self.areas_limit != other.areas_limit
+            || self.areas_labels != other.areas_labels
+            || self.zones_limit != other.zones_limit
+            || self.zones_labels != other.zones_labels
+            || self.zones_types != other.zones_types
+            || self.pgm_ob_limit != other.pgm_ob_limit
+            || self.pgm_ob_labels != other.pgm_ob_labels
+            || self.pgm_ob_control != other.pgm_ob_control
+            || self.doors_limit != other.doors_limit
+            || self.doors_labels != other.doors_labels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile() -> DeviceProfile {
+        DeviceProfile {
+            areas_limit: 1,
+            areas_labels: vec!["House".to_string()],
+            zones_limit: 2,
+            zones_labels: vec!["Front Door".to_string(), "Kitchen".to_string()],
+            zones_types: vec![0, 0],
+            pgm_limit: 1,
+            pgm_labels: vec!["Gate".to_string()],
+            pgm_control: vec!["111".to_string()],
+            ukeys_limit: 1,
+            ukeys_labels: vec!["Panic".to_string()],
+            ukeys_control: vec![1],
+            pgm_ob_limit: 1,
+            pgm_ob_labels: vec!["Lights".to_string()],
+            pgm_ob_control: vec!["111".to_string()],
+            doors_limit: 0,
+            doors_labels: vec![],
+            ver: 1,
+        }
+    }
+
+    #[test]
+    fn same_profile_does_not_differ() {
+        assert!(!profile().entities_differ(&profile()));
+    }
+
+    #[test]
+    fn changes_outside_the_entities_do_not_differ() {
+        let mut other = profile();
+        other.ver = 2;
+        other.ukeys_labels = vec!["Medical".to_string()];
+        other.pgm_labels = vec!["Garage".to_string()];
+        assert!(!profile().entities_differ(&other));
+    }
+
+    #[test]
+    fn entity_changes_differ() {
+        let changes: Vec<fn(&mut DeviceProfile)> = vec![
+            |p| p.areas_limit = 2,
+            |p| p.areas_labels[0] = "Garage".to_string(),
+            |p| p.zones_limit = 3,
+            |p| p.zones_labels[1] = "Lounge".to_string(),
+            |p| p.zones_types[0] = 1,
+            |p| p.pgm_ob_limit = 2,
+            |p| p.pgm_ob_labels[0] = "Geyser".to_string(),
+            |p| p.pgm_ob_control[0] = "011".to_string(),
+            |p| p.doors_limit = 1,
+            |p| p.doors_labels = vec!["Gate".to_string()],
+        ];
+        for change in changes {
+            let mut other = profile();
+            change(&mut other);
+            assert!(profile().entities_differ(&other));
+        }
+    }
+}
@@ -1,14 +1,18 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone)]
+/// Olarm's notification triggers for a device, as configured in the Olarm app
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct DeviceTriggers {
     pub ver: i64,
     #[serde(rename = "lastCheck")]
     pub last_check: i64,
+    /// Arming reminder settings, one entry per area
     #[serde(rename = "areasRemind")]
     pub areas_remind: Vec<Vec<i64>>,
+    /// Idle alert setting per zone
     #[serde(rename = "zonesIdle")]
     pub zones_idle: Vec<i64>,
+    /// Zone watch settings, one entry per zone
     #[serde(rename = "zonesWatch")]
     pub zones_watch: Vec<Vec<i64>>,
 }
@@ -5,6 +5,7 @@ pub mod action;
 pub mod device;
 pub mod device_profile;
 pub mod device_state;
-mod device_triggers;
+pub mod device_triggers;
 pub mod event;
 pub mod power;
+pub mod zone_status;
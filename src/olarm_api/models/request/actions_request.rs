@@ -1,7 +1,7 @@
 ﻿use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ActionsRequest {
     #[serde(rename = "actionCmd")]
     pub action_cmd: ActionCmd,
@@ -35,11 +35,47 @@ pub enum ActionCmd {
     #[serde(rename = "pgm-pulse")]
     PgmPulse,
 
+    // On-board PGM
+    #[serde(rename = "pgmob-close")]
+    PgmObClose,
+    #[serde(rename = "pgmob-open")]
+    PgmObOpen,
+    #[serde(rename = "pgmob-pulse")]
+    PgmObPulse,
+
+    // Doors
+    #[serde(rename = "door-unlock")]
+    DoorUnlock,
+
     // Ukey
     #[serde(rename = "ukey-activate")]
     UkeyActivate,
 }
 
+impl ActionCmd {
+    /// Whether this command arms an area in any mode
+    pub fn is_arm(&self) -> bool {
+        matches!(self, ActionCmd::AreaArm | ActionCmd::AreaSleep | ActionCmd::AreaStay)
+    }
+
+    pub fn is_pgm_ob(&self) -> bool {
+        matches!(self, ActionCmd::PgmObClose | ActionCmd::PgmObOpen | ActionCmd::PgmObPulse)
+    }
+
+    /// Name of the command on the device's MQTT control channel. Only bypass and the area
+    /// commands have known names there, the others can only be sent over REST.
+    pub fn mqtt_name(&self) -> Option<&'static str> {
+        match self {
+            ActionCmd::ZoneBypass => Some("bypass"),
+            ActionCmd::AreaArm => Some("arm"),
+            ActionCmd::AreaSleep => Some("sleep"),
+            ActionCmd::AreaStay => Some("stay"),
+            ActionCmd::AreaDisarm => Some("disarm"),
+            _ => None,
+        }
+    }
+}
+
 impl Display for ActionCmd {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         // Get the serde rename value by deserializing to a Value first
@@ -67,16 +103,17 @@ impl MqttRequest {
             data: None,
         }
     }
-    pub fn from(request: ActionsRequest) -> Option<Self> {
-        let action_cmd = match request.action_cmd {
-            ActionCmd::ZoneBypass => "bypass",
-            ActionCmd::AreaArm => "arm",
-            ActionCmd::AreaSleep => "sleep",
-            ActionCmd::AreaStay => "stay",
-            ActionCmd::AreaDisarm => "disarm",
-            _ => return None,
-        };
-        Some(Self {
+}
+
+impl TryFrom<ActionsRequest> for MqttRequest {
+    type Error = anyhow::Error;
+
+    fn try_from(request: ActionsRequest) -> anyhow::Result<Self> {
+        let action_cmd = request
+            .action_cmd
+            .mqtt_name()
+            .ok_or_else(|| anyhow::anyhow!("{} can't be sent over MQTT, use REST", request.action_cmd))?;
+        Ok(Self {
             method: MqttRequestMethod::POST,
             data: Some(vec![action_cmd.to_string(), request.action_num]),
         })
@@ -89,3 +126,68 @@ pub enum MqttRequestMethod {
     #[serde(rename = "POST")]
     POST,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mqtt_payloads() {
+        for (action_cmd, payload) in [
+            (ActionCmd::ZoneBypass, Some(r#"{"method":"POST","data":["bypass","2"]}"#)),
+            (ActionCmd::AreaArm, Some(r#"{"method":"POST","data":["arm","2"]}"#)),
+            (ActionCmd::AreaSleep, Some(r#"{"method":"POST","data":["sleep","2"]}"#)),
+            (ActionCmd::AreaStay, Some(r#"{"method":"POST","data":["stay","2"]}"#)),
+            (ActionCmd::AreaDisarm, Some(r#"{"method":"POST","data":["disarm","2"]}"#)),
+            // REST only
+            (ActionCmd::ZoneUnBypass, None),
+            (ActionCmd::PgmClose, None),
+            (ActionCmd::PgmOpen, None),
+            (ActionCmd::PgmPulse, None),
+            (ActionCmd::UkeyActivate, None),
+            (ActionCmd::PgmObClose, None),
+            (ActionCmd::PgmObOpen, None),
+            (ActionCmd::PgmObPulse, None),
+            (ActionCmd::DoorUnlock, None),
+        ] {
+            let request = ActionsRequest {
+                action_cmd,
+                action_num: "2".to_string(),
+            };
+            let mqtt_payload = MqttRequest::try_from(request)
+                .ok()
+                .map(|request| serde_json::to_string(&request).unwrap());
+            assert_eq!(mqtt_payload.as_deref(), payload, "{}", action_cmd);
+        }
+    }
+
+    #[test]
+    fn get_request_has_no_data() {
+        assert_eq!(serde_json::to_string(&MqttRequest::get()).unwrap(), r#"{"method":"GET"}"#);
+    }
+
+    #[test]
+    fn action_cmd_rest_names_round_trip() {
+        for (action_cmd, name) in [
+            (ActionCmd::ZoneBypass, "zone-bypass"),
+            (ActionCmd::ZoneUnBypass, "zone-unbypass"),
+            (ActionCmd::AreaArm, "area-arm"),
+            (ActionCmd::AreaSleep, "area-sleep"),
+            (ActionCmd::AreaStay, "area-stay"),
+            (ActionCmd::AreaDisarm, "area-disarm"),
+            (ActionCmd::PgmClose, "pgm-close"),
+            (ActionCmd::PgmOpen, "pgm-open"),
+            (ActionCmd::PgmPulse, "pgm-pulse"),
+            (ActionCmd::UkeyActivate, "ukey-activate"),
+            (ActionCmd::PgmObClose, "pgmob-close"),
+            (ActionCmd::PgmObOpen, "pgmob-open"),
+            (ActionCmd::PgmObPulse, "pgmob-pulse"),
+            (ActionCmd::DoorUnlock, "door-unlock"),
+        ] {
+            let json = serde_json::to_string(&action_cmd).unwrap();
+            assert_eq!(json, format!("\"{}\"", name));
+            let parsed: ActionCmd = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed.to_string(), action_cmd.to_string());
+        }
+    }
+}
@@ -1,8 +1,8 @@
 use crate::olarm_api::models::event::Event;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
-struct DeviceEventsResponse {
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DeviceEventsResponse {
     pub page: i64,
     #[serde(rename = "pageLength")]
     pub page_length: i64,
@@ -77,6 +77,7 @@ pub struct UserResponse {
     #[serde(rename = "popiUrl")]
     pub popi_url: String,
 }
+#[allow(dead_code)]
 #[derive(Serialize, Deserialize)]
 struct Struct2 {
     #[serde(rename = "userId")]
@@ -0,0 +1,127 @@
+use dashmap::DashSet;
+use std::sync::LazyLock;
+use tracing::warn;
+
+/// Unknown status codes already logged, so each is only warned about once
+static LOGGED_UNKNOWN: LazyLock<DashSet<String>> = LazyLock::new(DashSet::new);
+
+/// Status code Olarm reports for each zone in `DeviceState.zones`.
+///
+/// Olarm doesn't publish a list of these codes. `a` and `b` are the ones the bridge has always
+/// handled and `c` is what idle zones report. `t`, `f` and `d` are assumed from the panel states
+/// they name and are unconfirmed, so their HA sensors are only created once a zone reports them.
+/// Other codes are logged as unknown, the zone is treated as closed, and the code is still shown
+/// in the zone's `zone_status` attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZoneStatus {
+    /// `c`: closed / idle
+    Closed,
+    /// `a`: open or triggered
+    Active,
+    /// `b`: bypassed
+    Bypassed,
+    /// `t`: tamper
+    Tamper,
+    /// `f`: fault / trouble
+    Trouble,
+    /// `d`: disabled
+    Disabled,
+    /// Any other code, kept as reported
+    Unknown(String),
+}
+
+impl ZoneStatus {
+    pub fn is_active(&self) -> bool {
+        matches!(self, ZoneStatus::Active)
+    }
+
+    pub fn is_bypassed(&self) -> bool {
+        matches!(self, ZoneStatus::Bypassed)
+    }
+
+    pub fn is_tamper(&self) -> bool {
+        matches!(self, ZoneStatus::Tamper)
+    }
+
+    pub fn is_trouble(&self) -> bool {
+        matches!(self, ZoneStatus::Trouble)
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        matches!(self, ZoneStatus::Disabled)
+    }
+
+    pub fn code(&self) -> &str {
+        match self {
+            ZoneStatus::Closed => "c",
+            ZoneStatus::Active => "a",
+            ZoneStatus::Bypassed => "b",
+            ZoneStatus::Tamper => "t",
+            ZoneStatus::Trouble => "f",
+            ZoneStatus::Disabled => "d",
+            ZoneStatus::Unknown(code) => code,
+        }
+    }
+}
+
+impl From<&str> for ZoneStatus {
+    fn from(code: &str) -> Self {
+        match code.to_ascii_lowercase().as_str() {
+            "c" => ZoneStatus::Closed,
+            "a" => ZoneStatus::Active,
+            "b" => ZoneStatus::Bypassed,
+            "t" => ZoneStatus::Tamper,
+            "f" => ZoneStatus::Trouble,
+            "d" => ZoneStatus::Disabled,
+            _ => {
+                if first_sighting(code) {
+                    warn!("Unknown zone status code {:?}, treating the zone as closed", code);
+                }
+                ZoneStatus::Unknown(code.to_string())
+            }
+        }
+    }
+}
+
+/// Whether the unknown code is seen for the first time, and so should be logged
+fn first_sighting(code: &str) -> bool {
+    LOGGED_UNKNOWN.insert(code.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_known_codes_ignoring_case() {
+        for (code, status) in [
+            ("c", ZoneStatus::Closed),
+            ("a", ZoneStatus::Active),
+            ("b", ZoneStatus::Bypassed),
+            ("t", ZoneStatus::Tamper),
+            ("f", ZoneStatus::Trouble),
+            ("d", ZoneStatus::Disabled),
+        ] {
+            assert_eq!(ZoneStatus::from(code), status);
+            assert_eq!(ZoneStatus::from(code.to_uppercase().as_str()), status);
+            assert_eq!(status.code(), code);
+        }
+    }
+
+    #[test]
+    fn keeps_unknown_codes_as_reported() {
+        let status = ZoneStatus::from("x");
+        assert_eq!(status, ZoneStatus::Unknown("x".to_string()));
+        assert_eq!(status.code(), "x");
+        assert!(!status.is_active() && !status.is_bypassed());
+        assert!(!status.is_tamper() && !status.is_trouble() && !status.is_disabled());
+    }
+
+    #[test]
+    fn unknown_codes_are_logged_once() {
+        let _ = ZoneStatus::from("zone_status_test_code");
+        assert!(!first_sighting("zone_status_test_code"));
+        assert!(first_sighting("zone_status_other_test_code"));
+        assert!(!first_sighting("zone_status_other_test_code"));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::olarm_api::models::response::device_events_response::DeviceEventsResponse;
 use crate::olarm_api::models::response::device_response::DeviceResponse;
 use crate::olarm_api::models::response::devices_response::DevicesResponse;
 use crate::olarm_api::models::response::get_actions_response::GetActionsResponse;
@@ -159,6 +160,17 @@ impl OlarmApiTrait for OlarmClient {
             .with_context(|| format!("Unable to deserialize response. Body was: \"{}\"", contents))
     }
 
+    async fn get_events(&self, device_id: &str) -> anyhow::Result<DeviceEventsResponse> {
+        let url = format!(
+            "https://apiv4.olarm.co/api/v4/devices/{}/events",
+            device_id
+        );
+        let response = self.client.get(url).send().await?;
+        let contents = response.text().await?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Unable to deserialize response. Body was: \"{}\"", contents))
+    }
+
     async fn get_oauth_response(&self) -> anyhow::Result<LoginViaUserCredentialsResponse> {
         // First, check if we have a valid non-expired token
         {
@@ -256,6 +268,10 @@ pub trait OlarmApiTrait {
         &self,
         device_id: &str,
     ) -> impl std::future::Future<Output = anyhow::Result<GetActionsResponse>> + Send;
+    fn get_events(
+        &self,
+        device_id: &str,
+    ) -> impl std::future::Future<Output = anyhow::Result<DeviceEventsResponse>> + Send;
     fn get_oauth_response(&self) -> impl std::future::Future<Output = anyhow::Result<LoginViaUserCredentialsResponse>> + Send;
     fn refresh_oauth_token(
         &self,
@@ -288,6 +304,10 @@ where
         self.as_ref().get_actions(device_id).await
     }
 
+    async fn get_events(&self, device_id: &str) -> anyhow::Result<DeviceEventsResponse> {
+        self.as_ref().get_events(device_id).await
+    }
+
     async fn get_oauth_response(&self) -> anyhow::Result<LoginViaUserCredentialsResponse> {
         self.as_ref().get_oauth_response().await
     }
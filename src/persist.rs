@@ -0,0 +1,64 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Distinguishes the temp files of concurrent writes
+static WRITE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Writes `value` as JSON to `path` on the blocking pool. The content goes to a temp file unique
+/// to this write and is then renamed over `path`, so readers never see a partial file.
+pub async fn write_json<T: Serialize>(path: &Path, value: &T) -> anyhow::Result<()> {
+    let content = serde_json::to_string_pretty(value)?;
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || write_atomic(&path, content)).await?
+}
+
+fn write_atomic(path: &Path, content: String) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let temp_path = temp_path(path);
+    let result = std::fs::write(&temp_path, content).and_then(|_| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    Ok(result?)
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        WRITE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_writes_leave_a_complete_file() {
+        let dir = std::env::temp_dir().join(format!("olarm_persist_{}", std::process::id()));
+        let path = dir.join("state.json");
+        let writes = (0..20u64)
+            .map(|n| {
+                let path = path.clone();
+                tokio::spawn(async move { write_json(&path, &vec![n; 1000]).await })
+            })
+            .collect::<Vec<_>>();
+        for write in writes {
+            write.await.unwrap().unwrap();
+        }
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let values: Vec<u64> = serde_json::from_str(&content).unwrap();
+        assert_eq!(values.len(), 1000);
+        assert!(values.iter().all(|value| *value == values[0]));
+        let leftovers = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(leftovers, 1, "temp files were left behind");
+    }
+}
@@ -0,0 +1,215 @@
+use crate::{Account, SenderMap};
+use crate::home_assistant::availability::{Availability, AvailabilityState};
+use crate::home_assistant::button::ButtonDiscoveryPayload;
+use crate::home_assistant::device::{BRIDGE_DEVICE_IDENTIFIER, Device};
+use crate::home_assistant::discovery_registry::DiscoveryRegistry;
+use crate::home_assistant::sensor::SensorDiscoveryPayload;
+use crate::olarm_api::cached_olarm_client::{ApiError, CachedOlarmClient};
+use crate::olarm_api::olarm_client::{OlarmApiTrait, OlarmClient};
+use crate::topics::TopicBuilder;
+use chrono::{DateTime, Utc};
+use dashmap::DashSet;
+use rumqttc::QoS;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{error, warn};
+
+/// Supervisor state shared between `main` and the device connection tasks
+pub struct BridgeState {
+    pub started_at: DateTime<Utc>,
+    /// Ids of devices with a live Olarm MQTT connection
+    pub connected_devices: DashSet<String>,
+    /// Bumped to make every device task drop and re-establish its connection
+    pub restart_tx: watch::Sender<u64>,
+    pub discovery: Arc<DiscoveryRegistry>,
+}
+
+impl BridgeState {
+    pub fn new(discovery: Arc<DiscoveryRegistry>) -> Self {
+        Self {
+            started_at: Utc::now(),
+            connected_devices: DashSet::new(),
+            restart_tx: watch::Sender::new(0),
+            discovery,
+        }
+    }
+
+    pub fn restart_all(&self) {
+        warn!("Restarting all device connections");
+        self.restart_tx.send_modify(|generation| *generation += 1);
+    }
+}
+
+/// Publishes the bridge's own HA device with its diagnostic entities
+#[derive(Clone)]
+pub struct BridgeProcessor {
+    pub ha_client: rumqttc::AsyncClient,
+    pub accounts: Vec<Account<CachedOlarmClient<OlarmClient>>>,
+    pub senders: SenderMap,
+    pub bridge_state: Arc<BridgeState>,
+    pub published_discovery: Arc<DashSet<String>>,
+    pub topics: Arc<TopicBuilder>,
+}
+
+impl BridgeProcessor {
+    pub async fn run(&self, interval: Duration) {
+        let mut tick = tokio::time::interval(interval);
+        loop {
+            tick.tick().await;
+            if let Err(e) = self.publish().await {
+                error!("Error occurred while publishing bridge state: {:?}", e);
+            }
+        }
+    }
+
+    async fn publish(&self) -> anyhow::Result<()> {
+        if self.published_discovery.insert(Self::unique_id("restart")) {
+            self.publish_discovery().await?;
+        }
+
+        self.ha_client
+            .publish(self.topics.bridge("availability"), QoS::AtLeastOnce, true, AvailabilityState::Online.as_serde_value())
+            .await?;
+
+        let uptime = (Utc::now() - self.bridge_state.started_at).num_seconds();
+        let queue_depth: usize = self
+            .senders
+            .read()
+            .await
+            .values()
+            .map(|sender| sender.max_capacity() - sender.capacity())
+            .sum();
+        // With several accounts, the token expiring first and the most recent error are shown
+        let mut token_expiry: Option<DateTime<Utc>> = None;
+        let mut last_error: Option<ApiError> = None;
+        for account in &self.accounts {
+            if let Ok(response) = account.olarm_client.get_oauth_response().await
+                && let Some(expiry) = DateTime::from_timestamp(response.oat_expire as i64, 0)
+            {
+                token_expiry = Some(token_expiry.map_or(expiry, |earliest| earliest.min(expiry)));
+            }
+            if let Some(error) = account.olarm_client.last_error().await
+                && last_error.as_ref().is_none_or(|latest| error.occurred_at > latest.occurred_at)
+            {
+                last_error = Some(error);
+            }
+        }
+        let token_expiry = token_expiry.map(|expiry| expiry.to_rfc3339());
+
+        let states = [
+            ("version", env!("CARGO_PKG_VERSION").to_string()),
+            ("uptime", uptime.to_string()),
+            ("connected_devices", self.bridge_state.connected_devices.len().to_string()),
+            ("command_queue_depth", queue_depth.to_string()),
+            // HA shows "None" as unknown
+            ("token_expiry", token_expiry.unwrap_or_else(|| "None".to_string())),
+            (
+                "last_rest_error",
+                last_error
+                    .as_ref()
+                    .map(|error| error.message.clone())
+                    .unwrap_or_else(|| "None".to_string()),
+            ),
+        ];
+        for (key, state) in states {
+            self.ha_client
+                .publish(self.topics.bridge(&format!("{}/state", key)), QoS::AtMostOnce, true, state)
+                .await?;
+        }
+
+        let last_error_attributes = serde_json::json!({
+            "occurred_at": last_error.map(|error| error.occurred_at.to_rfc3339()),
+        });
+        self.ha_client
+            .publish(
+                self.topics.bridge("last_rest_error/attributes"),
+                QoS::AtMostOnce,
+                true,
+                last_error_attributes.to_string(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn publish_discovery(&self) -> anyhow::Result<()> {
+        let availability = vec![Availability {
+            payload_available: Some(AvailabilityState::Online.as_serde_value()),
+            payload_not_available: Some(AvailabilityState::Offline.as_serde_value()),
+            topic: self.topics.bridge("availability"),
+            value_template: None,
+        }];
+
+        let sensors = [
+            ("version", "Version", None, None, None, "mdi:tag"),
+            ("uptime", "Uptime", Some("duration"), Some("s"), Some("total_increasing"), "mdi:timer-outline"),
+            ("connected_devices", "Connected Devices", None, None, Some("measurement"), "mdi:lan-connect"),
+            ("command_queue_depth", "Command Queue Depth", None, None, Some("measurement"), "mdi:tray-full"),
+            ("token_expiry", "Token Expiry", Some("timestamp"), None, None, "mdi:key-chain"),
+            ("last_rest_error", "Last Olarm REST Error", None, None, None, "mdi:alert-circle-outline"),
+        ];
+        for (key, name, device_class, unit, state_class, icon) in sensors {
+            let unique_id = Self::unique_id(key);
+            let discovery_object = SensorDiscoveryPayload {
+                device: Device::bridge(),
+                name: format!("Olarm MQTT Bridge {}", name),
+                unique_id: unique_id.clone(),
+                state_topic: self.topics.bridge(&format!("{}/state", key)),
+                device_class: device_class.map(str::to_string),
+                state_class: state_class.map(str::to_string),
+                unit_of_measurement: unit.map(str::to_string),
+                icon: Some(icon.to_string()),
+                entity_category: Some("diagnostic".to_string()),
+                json_attributes_topic: (key == "last_rest_error")
+                    .then(|| self.topics.bridge("last_rest_error/attributes")),
+                availability: Some(availability.clone()),
+                availability_mode: None, //defaults to "latest"
+            };
+            self.bridge_state
+                .discovery
+                .publish(
+                    &self.ha_client,
+                    BRIDGE_DEVICE_IDENTIFIER,
+                    &self.topics.discovery("sensor", &unique_id),
+                    serde_json::to_string(&discovery_object)?,
+                )
+                .await?;
+        }
+
+        let unique_id = Self::unique_id("restart");
+        let restart_object = ButtonDiscoveryPayload {
+            device: Device::bridge(),
+            name: "Olarm MQTT Bridge Restart Device Connections".to_string(),
+            unique_id: unique_id.clone(),
+            command_topic: self.topics.bridge("restart/set"),
+            payload_press: Some("restart".to_string()),
+            icon: Some("mdi:restart".to_string()),
+            entity_category: Some("diagnostic".to_string()),
+            json_attributes_topic: None,
+            availability: Some(availability),
+            availability_mode: None, //defaults to "latest"
+        };
+        self.bridge_state
+            .discovery
+            .publish(
+                &self.ha_client,
+                BRIDGE_DEVICE_IDENTIFIER,
+                &self.topics.discovery("button", &unique_id),
+                serde_json::to_string(&restart_object)?,
+            )
+            .await?;
+        self.ha_client
+            .subscribe(self.topics.bridge("restart/set"), QoS::AtLeastOnce)
+            .await?;
+        for account in &self.accounts {
+            self.ha_client
+                .subscribe(account.topics.device("+", "discovery/purge"), QoS::AtLeastOnce)
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn unique_id(key: &str) -> String {
+        format!("olarm_bridge_{}", key)
+    }
+}
@@ -1,13 +1,24 @@
-use crate::MqttCommand;
+use crate::{MqttCommand, ZoneObject};
+use crate::config::CommandTransport;
+use crate::home_assistant::alarm_control_panel::AlarmState;
+use crate::home_assistant::models::requests::arm_bypass::ArmBypassRequest;
+use crate::home_assistant::models::responses::area_command_result::{
+    AreaCommandResult, BypassedZone,
+};
 use crate::olarm_api::models::request::actions_request::{
     ActionCmd, ActionsRequest, MqttRequest,
 };
 use crate::olarm_api::olarm_client::OlarmApiTrait;
 use crate::processors::ProcessorState;
-use rumqttc::QoS;
+use crate::processors::panel_processor::PanelProcessor;
+use crate::processors::zones_processor::ZonesProcessor;
+use chrono::Utc;
+use rumqttc::{ClientError, QoS};
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
 use crate::throttled_mqtt_client::MqttThrottledClient;
+use crate::topics::TopicBuilder;
 
 #[derive(Clone)]
 pub struct HaProcessor<T>
@@ -17,6 +28,8 @@ where
     pub mqtt_olarm_client: MqttThrottledClient,
     pub http_olarm_client: T,
     pub(crate) processor_state: Arc<RwLock<ProcessorState>>,
+    pub ha_client: rumqttc::AsyncClient,
+    pub topics: Arc<TopicBuilder>,
 }
 
 impl<T: OlarmApiTrait + Clone + Send + Sync> HaProcessor<T> {
@@ -31,29 +44,182 @@ impl<T: OlarmApiTrait + Clone + Send + Sync> HaProcessor<T> {
             action_cmd,
             action_num: action_num.to_string(),
         };
-        if let ActionCmd::ZoneBypass = action_cmd {
-            let mqtt_payload = MqttRequest::from(payload).unwrap();
-        let s_payload = serde_json::to_string(&mqtt_payload).unwrap();
-            let control_topic = format!("si/app/v2/{}/control", imei);
-            self.mqtt_olarm_client
-                .publish_and_wait(
-                    &control_topic,
-                    QoS::AtLeastOnce,
-                    false,
-                    s_payload,
-                )
+        let transport = {
+            self.processor_state.read().await.device_config.command_transport
+        };
+        deliver_action(&self.mqtt_olarm_client, &self.http_olarm_client, transport, device_id, imei, payload).await
+    }
+
+    /// Sends an area command, refusing to arm if the device config asks for a readiness check
+    pub async fn handle_set_area(
+        &self,
+        device_id: &str,
+        imei: &str,
+        area_number: usize,
+        action_cmd: ActionCmd,
+    ) -> anyhow::Result<()> {
+        if let ActionCmd::AreaDisarm = action_cmd {
+            self.publish_disarming(device_id, area_number).await?;
+        }
+        let outcome = match self.check_ready_to_arm(area_number, action_cmd).await {
+            Ok(()) => self.handle_action(device_id, imei, action_cmd, area_number).await,
+            Err(e) => Err(e),
+        };
+        if outcome.is_err() && matches!(action_cmd, ActionCmd::AreaDisarm) {
+            self.restore_area_state(device_id, area_number).await?;
+        }
+        self.publish_area_result(device_id, area_number, action_cmd, Vec::new(), &outcome)
+            .await?;
+        outcome
+    }
+
+    /// Shows the area as `disarming` until the next status from the panel replaces it
+    async fn publish_disarming(&self, device_id: &str, area_number: usize) -> anyhow::Result<()> {
+        let (area_state, _) = self.area_snapshot(area_number).await;
+        if matches!(area_state.as_deref(), Some("disarm") | Some("notready") | None) {
+            return Ok(());
+        }
+        let state_topic = self.topics.area(device_id, area_number, "state");
+        self.ha_client
+            .publish(&state_topic, QoS::AtMostOnce, true, AlarmState::Disarming.as_serde_value())
+            .await?;
+        Ok(())
+    }
+
+    /// Republishes the last known area state, e.g. after a failed disarm left it at `disarming`
+    async fn restore_area_state(&self, device_id: &str, area_number: usize) -> anyhow::Result<()> {
+        let area_state = {
+            let state = self.processor_state.read().await;
+            state.latest_response.as_ref().and_then(|response| {
+                PanelProcessor::<T>::get_areas(response, &state.device_profile)
+                    .into_iter()
+                    .find(|area| area.area_number == area_number)
+                    .and_then(|area| area.state)
+            })
+        };
+        if let Some(area_state) = area_state {
+            let state_topic = self.topics.area(device_id, area_number, "state");
+            self.ha_client
+                .publish(&state_topic, QoS::AtMostOnce, true, area_state)
                 .await?;
+        }
+        Ok(())
+    }
+
+    async fn check_ready_to_arm(&self, area_number: usize, action_cmd: ActionCmd) -> anyhow::Result<()> {
+        let reject_when_not_ready = {
+            self.processor_state.read().await.device_config.reject_arm_when_not_ready
+        };
+        if !reject_when_not_ready || !action_cmd.is_arm() {
+            return Ok(());
+        }
+        let (area_state, open_zones) = self.area_snapshot(area_number).await;
+        if area_state.as_deref() == Some("notready") {
+            let open_zone_names = open_zones
+                .iter()
+                .map(|zone| zone.name.as_str())
+                .collect::<Vec<_>>();
+            anyhow::bail!(
+                "Area {} is not ready to arm. Open zones: {}",
+                area_number,
+                if open_zone_names.is_empty() {
+                    "unknown".to_string()
+                } else {
+                    open_zone_names.join(", ")
+                }
+            );
+        }
+        Ok(())
+    }
 
-        } else {
-            let _ = self
-                .http_olarm_client
-                .send_action(device_id, payload)
-                .await?
-                .error_for_status()?;
+    /// Latest raw Olarm state of the area and the zones currently open in it
+    async fn area_snapshot(&self, area_number: usize) -> (Option<String>, Vec<ZoneObject>) {
+        let state = self.processor_state.read().await;
+        match &state.latest_response {
+            Some(response) => {
+                let zones = ZonesProcessor::get_zones(
+                    response,
+                    &state.device_profile,
+                    &state.zone_types,
+                    &state.device_config,
+                );
+                (
+                    response.data.areas.get(area_number.wrapping_sub(1)).cloned(),
+                    ZonesProcessor::get_open_zones(&zones, &state.device_config, area_number),
+                )
+            }
+            None => (None, Vec::new()),
         }
+    }
 
+    async fn publish_area_result(
+        &self,
+        device_id: &str,
+        area_number: usize,
+        action_cmd: ActionCmd,
+        bypassed_zones: Vec<BypassedZone>,
+        outcome: &anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        if let Err(e) = outcome {
+            warn!("Command {} for area {} on {} failed: {:#}", action_cmd, area_number, device_id, e);
+        }
+        let result = AreaCommandResult {
+            action_cmd,
+            success: outcome.is_ok(),
+            bypassed_zones,
+            message: outcome.as_ref().err().map(|e| format!("{:#}", e)),
+            timestamp: Utc::now().to_rfc3339(),
+        };
+        let result_topic = self.topics.area(device_id, area_number, "result");
+        self.ha_client
+            .publish(&result_topic, QoS::AtLeastOnce, true, serde_json::to_string(&result)?)
+            .await?;
         Ok(())
     }
+
+    /// Bypasses every active zone in the area, then arms it
+    pub async fn handle_arm_with_bypass(
+        &self,
+        device_id: &str,
+        imei: &str,
+        area_number: usize,
+        request: ArmBypassRequest,
+    ) -> anyhow::Result<()> {
+        let (_, open_zones) = self.area_snapshot(area_number).await;
+
+        let mut bypassed_zones = Vec::new();
+        let mut outcome: anyhow::Result<()> = Ok(());
+        for zone in open_zones {
+            if let Err(e) = self
+                .handle_action(device_id, imei, ActionCmd::ZoneBypass, zone.zone_number)
+                .await
+            {
+                outcome = Err(e.context(format!("Failed to bypass zone {}", zone.zone_number)));
+                break;
+            }
+            bypassed_zones.push(BypassedZone {
+                zone_number: zone.zone_number,
+                name: zone.name,
+            });
+        }
+
+        if outcome.is_ok() {
+            outcome = self
+                .handle_action(device_id, imei, request.action_cmd, area_number)
+                .await;
+        }
+
+        info!(
+            "Arm with bypass for area {} on {}: bypassed zones {:?}",
+            area_number,
+            device_id,
+            bypassed_zones.iter().map(|z| z.zone_number).collect::<Vec<_>>()
+        );
+        self.publish_area_result(device_id, area_number, request.action_cmd, bypassed_zones, &outcome)
+            .await?;
+        outcome
+    }
+
     pub async fn process_ha_command(
         &self,
         cmd: MqttCommand,
@@ -67,20 +233,207 @@ impl<T: OlarmApiTrait + Clone + Send + Sync> HaProcessor<T> {
                 area_number,
                 action_cmd,
             } => {
-                self.handle_action(&device_id, &imei, action_cmd, area_number)
+                self.handle_set_area(&device_id, &imei, area_number, action_cmd)
                     .await?;
             }
             MqttCommand::SetZoneBypass {
                 device_id,
                 zone_number,
+                payload,
+            } => {
+                self.handle_action(&device_id, &imei, payload.action_cmd(), zone_number)
+                    .await?;
+            }
+            MqttCommand::ArmWithBypass {
+                device_id,
+                area_number,
+                payload,
+            } => {
+                self.handle_arm_with_bypass(&device_id, &imei, area_number, payload)
+                    .await?;
+            }
+            MqttCommand::SetPgmOb {
+                device_id,
+                pgm_number,
+                action_cmd,
+            } => {
+                self.handle_action(&device_id, &imei, action_cmd, pgm_number)
+                    .await?;
+            }
+            MqttCommand::SetDoor {
+                device_id,
+                door_number,
                 action_cmd,
-                payload: _payload
             } => {
-                self.handle_action(&device_id, &imei, action_cmd, zone_number)
+                self.handle_action(&device_id, &imei, action_cmd, door_number)
                     .await?;
             }
+            MqttCommand::PurgeDiscovery { device_id } => {
+                // The HA router purges directly, so this never reaches a device's queue
+                warn!("Ignoring discovery purge for {} sent to the device queue", device_id);
+            }
         }
 
         Ok(())
     }
 }
+
+/// Sends an action to the device over `transport`
+async fn deliver_action<T: OlarmApiTrait>(
+    mqtt_client: &MqttThrottledClient,
+    http_client: &T,
+    transport: CommandTransport,
+    device_id: &str,
+    imei: &str,
+    payload: ActionsRequest,
+) -> anyhow::Result<()> {
+    let action_cmd = payload.action_cmd;
+    let action_num = payload.action_num.clone();
+    if action_cmd.mqtt_name().is_none() {
+        debug!("{} has no MQTT command, sending it over REST", action_cmd);
+        return send_rest_action(http_client, device_id, payload).await;
+    }
+    match transport {
+        CommandTransport::Mqtt => send_mqtt_action(mqtt_client, imei, payload).await,
+        CommandTransport::Rest => send_rest_action(http_client, device_id, payload).await,
+        // A publish while disconnected only waits in the client's queue until it times out
+        CommandTransport::MqttWithRestFallback if !mqtt_client.is_connected() => {
+            warn!(
+                "Olarm MQTT connection of {} is down, sending {} {} over REST",
+                device_id, action_cmd, action_num
+            );
+            send_rest_action(http_client, device_id, payload).await
+        }
+        CommandTransport::MqttWithRestFallback => {
+            match send_mqtt_action(mqtt_client, imei, payload.clone()).await {
+                // The command never reached the broker, so sending it over REST can't repeat it
+                Err(e) if e.downcast_ref::<ClientError>().is_some() => {
+                    warn!(
+                        "MQTT command {} {} failed, falling back to REST: {:?}",
+                        action_cmd, action_num, e
+                    );
+                    send_rest_action(http_client, device_id, payload).await
+                }
+                // It was published and may still be applied. Resending would run toggling
+                // commands like pulse and bypass twice.
+                Err(e) => Err(e.context(format!(
+                    "MQTT command {} {} was sent but not confirmed, not resending over REST",
+                    action_cmd, action_num
+                ))),
+                Ok(()) => Ok(()),
+            }
+        }
+    }
+}
+
+async fn send_mqtt_action(mqtt_client: &MqttThrottledClient, imei: &str, payload: ActionsRequest) -> anyhow::Result<()> {
+    let s_payload = serde_json::to_string(&MqttRequest::try_from(payload)?)?;
+    let control_topic = format!("si/app/v2/{}/control", imei);
+    mqtt_client
+        .publish_and_wait(&control_topic, QoS::AtLeastOnce, false, s_payload)
+        .await
+}
+
+async fn send_rest_action<T: OlarmApiTrait>(http_client: &T, device_id: &str, payload: ActionsRequest) -> anyhow::Result<()> {
+    let _ = http_client
+        .send_action(device_id, payload)
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::olarm_api::models::response::device_events_response::DeviceEventsResponse;
+    use crate::olarm_api::models::response::device_response::DeviceResponse;
+    use crate::olarm_api::models::response::devices_response::DevicesResponse;
+    use crate::olarm_api::models::response::get_actions_response::GetActionsResponse;
+    use crate::olarm_api::models::response::login_via_user_credentials_response::LoginViaUserCredentialsResponse;
+    use crate::olarm_api::models::response::refresh_oauth_token_response::RefreshOAuthTokenResponse;
+    use crate::olarm_api::models::response::user_response::UserResponse;
+    use rumqttc::{AsyncClient, MqttOptions};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Records the actions sent over REST and accepts them
+    #[derive(Default)]
+    struct RestActions {
+        sent: Mutex<Vec<String>>,
+    }
+
+    impl OlarmApiTrait for RestActions {
+        async fn get_user(&self, _: &str) -> anyhow::Result<UserResponse> {
+            unimplemented!()
+        }
+        async fn get_devices(&self) -> anyhow::Result<DevicesResponse> {
+            unimplemented!()
+        }
+        async fn get_device(&self, _: &str) -> anyhow::Result<DeviceResponse> {
+            unimplemented!()
+        }
+        async fn send_action(&self, device_id: &str, payload: ActionsRequest) -> anyhow::Result<reqwest::Response> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push(format!("{} {} {}", device_id, payload.action_cmd, payload.action_num));
+            Ok(http::Response::new("").into())
+        }
+        async fn get_actions(&self, _: &str) -> anyhow::Result<GetActionsResponse> {
+            unimplemented!()
+        }
+        async fn get_events(&self, _: &str) -> anyhow::Result<DeviceEventsResponse> {
+            unimplemented!()
+        }
+        async fn get_oauth_response(&self) -> anyhow::Result<LoginViaUserCredentialsResponse> {
+            unimplemented!()
+        }
+        async fn refresh_oauth_token(&self, _: &str) -> anyhow::Result<RefreshOAuthTokenResponse> {
+            unimplemented!()
+        }
+    }
+
+    fn action(action_cmd: ActionCmd) -> ActionsRequest {
+        ActionsRequest {
+            action_cmd,
+            action_num: "1".to_string(),
+        }
+    }
+
+    /// A client whose event loop never runs, as when the Olarm broker can't be reached
+    fn disconnected_client() -> MqttThrottledClient {
+        let (client, event_loop) = AsyncClient::new(MqttOptions::new("test", "localhost", 1883), 10);
+        // Keep the request channel open, so publishes are queued rather than refused
+        std::mem::forget(event_loop);
+        MqttThrottledClient::new(client)
+    }
+
+    #[tokio::test]
+    async fn fallback_uses_rest_while_mqtt_is_disconnected() {
+        let rest = RestActions::default();
+        let result = tokio::time::timeout(
+            Duration::from_secs(1),
+            deliver_action(
+                &disconnected_client(),
+                &rest,
+                CommandTransport::MqttWithRestFallback,
+                "abc",
+                "imei",
+                action(ActionCmd::AreaArm),
+            ),
+        )
+        .await
+        .expect("REST should be used without waiting on MQTT");
+        result.unwrap();
+        assert_eq!(*rest.sent.lock().unwrap(), ["abc \"area-arm\" 1"]);
+    }
+
+    #[tokio::test]
+    async fn commands_without_an_mqtt_name_use_rest() {
+        let rest = RestActions::default();
+        deliver_action(&disconnected_client(), &rest, CommandTransport::Mqtt, "abc", "imei", action(ActionCmd::PgmPulse))
+            .await
+            .unwrap();
+        assert_eq!(*rest.sent.lock().unwrap(), ["abc \"pgm-pulse\" 1"]);
+    }
+}
@@ -1,12 +1,20 @@
-﻿use std::sync::{Arc};
+﻿use std::collections::HashMap;
+use std::sync::{Arc};
 use dashmap::DashSet;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, watch};
 use crate::olarm_api::models::response::mqtt_device_response::MqttDeviceResponse;
 use crate::olarm_api::models::response::user_response::UserDevice;
+use crate::config::{DeviceConfig, ZoneTypeConfig};
+use crate::processors::panel_processor::AreaCountdown;
+use crate::processors::zones_processor::DebouncedZone;
 
+pub mod bridge_processor;
 pub mod ha_processor;
+pub mod outputs_processor;
+pub mod triggers_processor;
 pub mod zones_processor;
 pub mod panel_processor;
+pub mod trip_counters;
 
 /// Trait for a processor that handles MQTT messages
 pub trait MqttDeviceResponseProcessor: Send + Sync + 'static {
@@ -16,5 +24,45 @@ pub trait MqttDeviceResponseProcessor: Send + Sync + 'static {
 pub struct ProcessorState {
     pub device_profile: crate::olarm_api::models::device_profile::DeviceProfile,
     pub published_discovery: Arc<DashSet<String>>,
-    pub device: UserDevice
-}
\ No newline at end of file
+    pub device: UserDevice,
+    pub device_config: DeviceConfig,
+    /// Most recent status received from the device
+    pub latest_response: Option<MqttDeviceResponse>,
+    /// Raw Olarm state of each area from the previous status, keyed by area number
+    pub previous_area_states: HashMap<usize, String>,
+    /// Whether each zone was active in the previous status, keyed by zone number
+    pub previous_zone_states: HashMap<usize, bool>,
+    /// Zone states last published to HA, keyed by zone number
+    pub zone_debounce: HashMap<usize, DebouncedZone>,
+    /// Entry/exit delays currently running, keyed by area number
+    pub area_countdowns: HashMap<usize, AreaCountdown>,
+    /// Zone type overrides from the config, keyed by Olarm type code
+    pub zone_types: HashMap<String, ZoneTypeConfig>,
+    /// Closes when the device's connection ends. Tasks spawned for the connection select on it.
+    pub session: watch::Receiver<()>,
+}
+
+impl ProcessorState {
+    /// Completes once the connection `session` was taken from has ended
+    pub async fn session_ended(mut session: watch::Receiver<()>) {
+        // Nothing is ever sent, so this only returns when the sender is dropped
+        while session.changed().await.is_ok() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn session_ends_when_the_connection_drops_its_sender() {
+        let (session_tx, session) = watch::channel(());
+        let ended = tokio::spawn(ProcessorState::session_ended(session));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!ended.is_finished());
+
+        drop(session_tx);
+        tokio::time::timeout(Duration::from_secs(1), ended).await.unwrap().unwrap();
+    }
+}
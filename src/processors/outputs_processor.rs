@@ -0,0 +1,271 @@
+use crate::home_assistant::availability::{Availability, AvailabilityState};
+use crate::home_assistant::binary_sensor::BinarySensorDiscoveryPayload;
+use crate::home_assistant::button::ButtonDiscoveryPayload;
+use crate::home_assistant::device::Device;
+use crate::home_assistant::discovery_registry::DiscoveryRegistry;
+use crate::home_assistant::switch::SwitchDiscoveryPayload;
+use crate::olarm_api::models::device_profile::DeviceProfile;
+use crate::olarm_api::models::request::actions_request::ActionCmd;
+use crate::olarm_api::models::response::mqtt_device_response::MqttDeviceResponse;
+use crate::olarm_api::models::response::user_response::UserDevice;
+use crate::processors::{MqttDeviceResponseProcessor, ProcessorState};
+use crate::topics::TopicBuilder;
+use rumqttc::QoS;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// How an on-board PGM may be controlled, from the profile's `pgmObControl` flags:
+/// enabled, open/close and pulse, e.g. `"111"`
+#[derive(Debug, Clone, Copy)]
+pub struct PgmObControl {
+    pub enabled: bool,
+    pub open_close: bool,
+    pub pulse: bool,
+}
+
+impl PgmObControl {
+    pub fn parse(flags: &str) -> Self {
+        let flag = |index: usize| flags.chars().nth(index) == Some('1');
+        Self {
+            enabled: flag(0),
+            open_close: flag(1),
+            pulse: flag(2),
+        }
+    }
+}
+
+/// Publishes switches and pulse buttons for on-board PGMs, and unlock buttons for doors. Their
+/// commands have no known MQTT names, so they are always sent over REST.
+#[derive(Clone)]
+pub struct OutputsProcessor {
+    pub ha_client: rumqttc::AsyncClient,
+    pub topics: Arc<TopicBuilder>,
+    pub discovery: Arc<DiscoveryRegistry>,
+}
+
+impl OutputsProcessor {
+    fn label(labels: &[String], index: usize, fallback: &str) -> String {
+        labels
+            .get(index)
+            .filter(|label| !label.is_empty())
+            .cloned()
+            .unwrap_or_else(|| format!("{} {}", fallback, index + 1))
+    }
+
+    fn availability(&self, device_id: &str) -> Vec<Availability> {
+        vec![Availability {
+            payload_available: Some(AvailabilityState::Online.as_serde_value()),
+            payload_not_available: Some(AvailabilityState::Offline.as_serde_value()),
+            topic: self.topics.device_availability(device_id),
+            value_template: None,
+        }]
+    }
+
+    pub async fn handle_pgm_ob(
+        &self,
+        device: &UserDevice,
+        device_profile: &DeviceProfile,
+        pgm_states: &[String],
+        processor_state: &Arc<RwLock<ProcessorState>>,
+    ) -> anyhow::Result<()> {
+        let pgm_count = (device_profile.pgm_ob_limit.max(0) as usize).min(pgm_states.len());
+        for (index, pgm_state) in pgm_states.iter().enumerate().take(pgm_count) {
+            let control = device_profile
+                .pgm_ob_control
+                .get(index)
+                .map(|flags| PgmObControl::parse(flags))
+                .unwrap_or(PgmObControl { enabled: false, open_close: false, pulse: false });
+            if !control.enabled {
+                continue;
+            }
+            let pgm_number = index + 1;
+            let label = Self::label(&device_profile.pgm_ob_labels, index, "PGM");
+            let command_topic = self.topics.pgm_ob(&device.id, pgm_number, "set");
+            let state_topic = self.topics.pgm_ob(&device.id, pgm_number, "state");
+
+            if control.open_close {
+                let unique_id = format!("olarm_{}_pgm_ob_{}", self.topics.device_key(&device.id), pgm_number);
+                let should_publish = processor_state
+                    .write()
+                    .await
+                    .published_discovery
+                    .insert(unique_id.clone());
+                if should_publish {
+                    let discovery_object = SwitchDiscoveryPayload {
+                        device: Device::from_user_device(device, &self.topics),
+                        name: self.topics.output_name(device, &label, ""),
+                        payload_off: Some(ActionCmd::PgmObClose.to_string()),
+                        payload_on: Some(ActionCmd::PgmObOpen.to_string()),
+                        state_off: Some("off".to_string()),
+                        state_on: Some("on".to_string()),
+                        state_topic: state_topic.clone(),
+                        unique_id: unique_id.clone(),
+                        json_attributes_topic: None,
+                        command_topic: command_topic.clone(),
+                        availability: Some(self.availability(&device.id)),
+                        availability_mode: None, //defaults to "latest"
+                        // State comes from the device's pgmOb status
+                        optimistic: Some(false),
+                    };
+                    self.discovery
+                        .publish(
+                            &self.ha_client,
+                            &self.topics.device_key(&device.id),
+                            &self.topics.discovery("switch", &unique_id),
+                            serde_json::to_string(&discovery_object)?,
+                        )
+                        .await?;
+                    self.ha_client.subscribe(&command_topic, QoS::AtLeastOnce).await?;
+                }
+            } else {
+                // Pulse-only PGMs can't be switched, but their state is still worth showing
+                let unique_id = format!("olarm_{}_pgm_ob_{}_state", self.topics.device_key(&device.id), pgm_number);
+                let should_publish = processor_state
+                    .write()
+                    .await
+                    .published_discovery
+                    .insert(unique_id.clone());
+                if should_publish {
+                    let discovery_object = BinarySensorDiscoveryPayload {
+                        device: Device::from_user_device(device, &self.topics),
+                        device_class: None,
+                        name: self.topics.output_name(device, &label, ""),
+                        payload_off: "off".to_string(),
+                        payload_on: "on".to_string(),
+                        state_topic: state_topic.clone(),
+                        unique_id: unique_id.clone(),
+                        off_delay: None,
+                        icon: Some("mdi:electric-switch".to_string()),
+                        entity_category: None,
+                        json_attributes_topic: None,
+                        availability: Some(self.availability(&device.id)),
+                        availability_mode: None, //defaults to "latest"
+                    };
+                    self.discovery
+                        .publish(
+                            &self.ha_client,
+                            &self.topics.device_key(&device.id),
+                            &self.topics.discovery("binary_sensor", &unique_id),
+                            serde_json::to_string(&discovery_object)?,
+                        )
+                        .await?;
+                }
+            }
+            let is_on = pgm_state.eq_ignore_ascii_case("a");
+            self.ha_client
+                .publish(&state_topic, QoS::AtMostOnce, true, if is_on { "on" } else { "off" })
+                .await?;
+
+            if control.pulse {
+                let unique_id = format!("olarm_{}_pgm_ob_{}_pulse", self.topics.device_key(&device.id), pgm_number);
+                let should_publish = processor_state
+                    .write()
+                    .await
+                    .published_discovery
+                    .insert(unique_id.clone());
+                if should_publish {
+                    let discovery_object = ButtonDiscoveryPayload {
+                        device: Device::from_user_device(device, &self.topics),
+                        name: self.topics.output_name(device, &label, "Pulse"),
+                        unique_id: unique_id.clone(),
+                        command_topic: command_topic.clone(),
+                        payload_press: Some(ActionCmd::PgmObPulse.to_string()),
+                        icon: Some("mdi:gesture-tap-button".to_string()),
+                        entity_category: None,
+                        json_attributes_topic: None,
+                        availability: Some(self.availability(&device.id)),
+                        availability_mode: None, //defaults to "latest"
+                    };
+                    self.discovery
+                        .publish(
+                            &self.ha_client,
+                            &self.topics.device_key(&device.id),
+                            &self.topics.discovery("button", &unique_id),
+                            serde_json::to_string(&discovery_object)?,
+                        )
+                        .await?;
+                    self.ha_client.subscribe(&command_topic, QoS::AtLeastOnce).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Doors report no state, so each gets an unlock button
+    pub async fn handle_doors(
+        &self,
+        device: &UserDevice,
+        device_profile: &DeviceProfile,
+        processor_state: &Arc<RwLock<ProcessorState>>,
+    ) -> anyhow::Result<()> {
+        for index in 0..device_profile.doors_limit.max(0) as usize {
+            let door_number = index + 1;
+            let unique_id = format!("olarm_{}_door_{}_unlock", self.topics.device_key(&device.id), door_number);
+            let should_publish = processor_state
+                .write()
+                .await
+                .published_discovery
+                .insert(unique_id.clone());
+            if !should_publish {
+                continue;
+            }
+            let label = Self::label(&device_profile.doors_labels, index, "Door");
+            let command_topic = self.topics.door(&device.id, door_number, "set");
+            let discovery_object = ButtonDiscoveryPayload {
+                device: Device::from_user_device(device, &self.topics),
+                name: self.topics.output_name(device, &label, "Unlock"),
+                unique_id: unique_id.clone(),
+                command_topic: command_topic.clone(),
+                payload_press: Some(ActionCmd::DoorUnlock.to_string()),
+                icon: Some("mdi:door-open".to_string()),
+                entity_category: None,
+                json_attributes_topic: None,
+                availability: Some(self.availability(&device.id)),
+                availability_mode: None, //defaults to "latest"
+            };
+            self.discovery
+                .publish(
+                    &self.ha_client,
+                    &self.topics.device_key(&device.id),
+                    &self.topics.discovery("button", &unique_id),
+                    serde_json::to_string(&discovery_object)?,
+                )
+                .await?;
+            self.ha_client.subscribe(&command_topic, QoS::AtLeastOnce).await?;
+        }
+        Ok(())
+    }
+}
+
+impl MqttDeviceResponseProcessor for OutputsProcessor {
+    async fn handle(
+        &self,
+        msg: MqttDeviceResponse,
+        processor_state: Arc<RwLock<ProcessorState>>,
+    ) -> anyhow::Result<()> {
+        let (device_profile, device) = {
+            let read_lock = processor_state.read().await;
+            (read_lock.device_profile.clone(), read_lock.device.clone())
+        };
+        self.handle_pgm_ob(&device, &device_profile, &msg.data.pgm_ob, &processor_state)
+            .await?;
+        self.handle_doors(&device, &device_profile, &processor_state).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pgm_ob_control_flags() {
+        let control = PgmObControl::parse("110");
+        assert!(control.enabled && control.open_close && !control.pulse);
+        let control = PgmObControl::parse("101");
+        assert!(control.enabled && !control.open_close && control.pulse);
+        // Missing flags are off
+        let control = PgmObControl::parse("1");
+        assert!(control.enabled && !control.open_close && !control.pulse);
+        assert!(!PgmObControl::parse("").enabled);
+    }
+}
@@ -1,22 +1,89 @@
-﻿use crate::{AreaObject};
+﻿use crate::{AreaObject, ZoneObject};
 use crate::olarm_api::models::device_profile::DeviceProfile;
 use crate::olarm_api::models::response::mqtt_device_response::MqttDeviceResponse;
 use crate::processors::{MqttDeviceResponseProcessor, ProcessorState};
-use chrono::DateTime;
+use chrono::{DateTime, Utc};
 use rumqttc::{QoS};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tracing::{warn};
 use crate::home_assistant::alarm_control_panel::{AlarmControlPanelDiscoveryPayload, AlarmFeature, AlarmState};
 use crate::home_assistant::availability::{Availability, AvailabilityState};
+use crate::home_assistant::binary_sensor::BinarySensorDiscoveryPayload;
+use crate::home_assistant::button::ButtonDiscoveryPayload;
+use crate::home_assistant::models::requests::arm_bypass::{ARM_CUSTOM_BYPASS_PAYLOAD, ArmBypassRequest};
+use crate::home_assistant::sensor::SensorDiscoveryPayload;
+use crate::config::DeviceConfig;
+use crate::home_assistant::discovery_registry::DiscoveryRegistry;
+use crate::topics::TopicBuilder;
 use crate::olarm_api::models::request::actions_request::ActionCmd;
+use crate::olarm_api::models::response::user_response::UserDevice;
 use crate::olarm_api::olarm_client::{OlarmApiTrait};
+use crate::processors::zones_processor::ZonesProcessor;
+
+/// Which delay Olarm's `countdown` area state represents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountdownKind {
+    Exit,
+    Entry,
+}
+
+impl CountdownKind {
+    /// Exit delay follows a disarmed area, entry delay follows an armed one.
+    /// Without a previous state, an open zone in the area suggests someone walked in.
+    pub fn infer(previous_state: Option<&str>, zones_open: bool) -> Self {
+        match previous_state {
+            Some("arm") | Some("stay") | Some("sleep") => CountdownKind::Entry,
+            Some("disarm") | Some("notready") => CountdownKind::Exit,
+            _ if zones_open => CountdownKind::Entry,
+            _ => CountdownKind::Exit,
+        }
+    }
+
+    pub fn alarm_state(&self) -> AlarmState {
+        match self {
+            CountdownKind::Exit => AlarmState::Arming,
+            CountdownKind::Entry => AlarmState::Pending,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CountdownKind::Exit => "exit",
+            CountdownKind::Entry => "entry",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AreaCountdown {
+    pub kind: CountdownKind,
+    pub started_at: DateTime<Utc>,
+    /// Unknown when no delay is configured for the area
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+impl AreaCountdown {
+    pub fn remaining_seconds(&self) -> Option<i64> {
+        self.ends_at
+            .map(|ends_at| (ends_at - Utc::now()).num_seconds().max(0))
+    }
+}
+
+/// Whether an area can be armed: disarmed, with no zones open. Armed, arming and alarmed areas
+/// aren't ready, as they can't be armed again.
+fn ready_to_arm(raw_state: &str, has_open_zones: bool) -> bool {
+    raw_state == "disarm" && !has_open_zones
+}
 
 #[derive(Clone)]
 pub struct PanelProcessor<T> where  T: OlarmApiTrait + Clone + Send + Sync + 'static {
     pub ha_client: rumqttc::AsyncClient,
     pub olarm_client: T,
+    pub topics: Arc<TopicBuilder>,
+    pub discovery: Arc<DiscoveryRegistry>,
 }
 
 impl<T: OlarmApiTrait + Clone + Send + Sync> PanelProcessor<T> {
@@ -48,12 +115,302 @@ impl<T: OlarmApiTrait + Clone + Send + Sync> PanelProcessor<T> {
                 panel_data.push(AreaObject {
                     name: area_label.to_string(),
                     state: area_state,
+                    raw_state: olarm_state.areas[area_num].clone(),
+                    detail: olarm_state.areas_detail.get(area_num).cloned(),
+                    last_changed: olarm_state
+                        .areas_stamp
+                        .get(area_num)
+                        .and_then(|stamp| DateTime::from_timestamp_millis(*stamp as i64)),
                     area_number: area_num + 1,
+                    own_device: area_count > 1,
                 })
             }
         }
         panel_data
     }
+
+    /// Publishes a binary sensor describing one aspect of an area's state
+    #[allow(clippy::too_many_arguments)]
+    pub async fn handle_area_binary_sensor(
+        &self,
+        device: &UserDevice,
+        area: &AreaObject,
+        key: &str,
+        label: &str,
+        device_class: Option<&str>,
+        is_on: bool,
+        attributes: Option<serde_json::Value>,
+        availability: Option<Vec<Availability>>,
+        should_publish: bool,
+    ) -> anyhow::Result<()> {
+        let unique_id = format!("olarm_{}_area_{}_{}", self.topics.device_key(&device.id), area.area_number, key);
+        let discovery_topic = self.topics.discovery("binary_sensor", &unique_id);
+        let state_topic = self.topics.area(&device.id, area.area_number, &format!("{}/state", key));
+        let json_attributes_topic = self.topics.area(&device.id, area.area_number, &format!("{}/attributes", key));
+
+        if should_publish {
+            let discovery_object = BinarySensorDiscoveryPayload {
+                device: area.ha_device(device, &self.topics),
+                device_class: device_class.map(str::to_string),
+                name: self.topics.area_entity_name(device, area.area_number, &area.name, label),
+                payload_off: "off".to_string(),
+                payload_on: "on".to_string(),
+                state_topic: state_topic.clone(),
+                unique_id: unique_id.clone(),
+                off_delay: None,
+                icon: None,
+                entity_category: None,
+                json_attributes_topic: attributes.as_ref().map(|_| json_attributes_topic.clone()),
+                availability,
+                availability_mode: None, //defaults to "latest"
+            };
+            self.discovery
+                .publish(&self.ha_client, &self.topics.device_key(&device.id), &discovery_topic, serde_json::to_string(&discovery_object)?)
+                .await?;
+        }
+
+        self.ha_client
+            .publish(&state_topic, QoS::AtMostOnce, true, if is_on { "on" } else { "off" })
+            .await?;
+        if let Some(attributes) = attributes {
+            self.ha_client
+                .publish(&json_attributes_topic, QoS::AtMostOnce, true, attributes.to_string())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Records the area's state and works out whether a countdown is an entry or exit delay.
+    /// Returns the running countdown, and whether it started with this status.
+    pub async fn track_countdown(
+        processor_state: &Arc<RwLock<ProcessorState>>,
+        device_config: &DeviceConfig,
+        area: &mut AreaObject,
+        zones_open: bool,
+    ) -> Option<(AreaCountdown, bool)> {
+        let mut state = processor_state.write().await;
+        let previous_state = state
+            .previous_area_states
+            .insert(area.area_number, area.raw_state.clone());
+        if area.raw_state != "countdown" {
+            state.area_countdowns.remove(&area.area_number);
+            return None;
+        }
+
+        if let Some(countdown) = state.area_countdowns.get(&area.area_number) {
+            area.state = Some(countdown.kind.alarm_state().as_serde_value());
+            return Some((countdown.clone(), false));
+        }
+
+        let kind = CountdownKind::infer(previous_state.as_deref(), zones_open);
+        let delay = match kind {
+            CountdownKind::Exit => device_config.exit_delay(area.area_number),
+            CountdownKind::Entry => device_config.entry_delay(area.area_number),
+        };
+        let started_at = area.last_changed.unwrap_or_else(Utc::now);
+        let countdown = AreaCountdown {
+            kind,
+            started_at,
+            ends_at: delay.map(|seconds| started_at + chrono::Duration::seconds(seconds as i64)),
+        };
+        state.area_countdowns.insert(area.area_number, countdown.clone());
+        area.state = Some(kind.alarm_state().as_serde_value());
+        Some((countdown, true))
+    }
+
+    /// Publishes the seconds left on the area's entry/exit delay, ticking every second while it runs
+    pub async fn handle_countdown_sensor(
+        &self,
+        device: &UserDevice,
+        area: &AreaObject,
+        countdown: Option<(AreaCountdown, bool)>,
+        availability: Vec<Availability>,
+        should_publish: bool,
+        processor_state: Arc<RwLock<ProcessorState>>,
+    ) -> anyhow::Result<()> {
+        let unique_id = format!("olarm_{}_area_{}_countdown", self.topics.device_key(&device.id), area.area_number);
+        let discovery_topic = self.topics.discovery("sensor", &unique_id);
+        let state_topic = self.topics.area(&device.id, area.area_number, "countdown/state");
+        let json_attributes_topic = self.topics.area(&device.id, area.area_number, "countdown/attributes");
+
+        if should_publish {
+            let discovery_object = SensorDiscoveryPayload {
+                device: area.ha_device(device, &self.topics),
+                name: self.topics.area_entity_name(device, area.area_number, &area.name, "Countdown"),
+                unique_id: unique_id.clone(),
+                state_topic: state_topic.clone(),
+                device_class: Some("duration".to_string()),
+                state_class: None,
+                unit_of_measurement: Some("s".to_string()),
+                icon: Some("mdi:timer-sand".to_string()),
+                entity_category: None,
+                json_attributes_topic: Some(json_attributes_topic.clone()),
+                availability: Some(availability),
+                availability_mode: None, //defaults to "latest"
+            };
+            self.discovery
+                .publish(&self.ha_client, &self.topics.device_key(&device.id), &discovery_topic, serde_json::to_string(&discovery_object)?)
+                .await?;
+        }
+
+        let Some((countdown, started)) = countdown else {
+            self.ha_client.publish(&state_topic, QoS::AtMostOnce, true, "0").await?;
+            self.ha_client
+                .publish(&json_attributes_topic, QoS::AtMostOnce, true, serde_json::json!({ "delay_type": null }).to_string())
+                .await?;
+            return Ok(());
+        };
+
+        let attributes = serde_json::json!({
+            "delay_type": countdown.kind.as_str(),
+            "started_at": countdown.started_at.to_rfc3339(),
+            "ends_at": countdown.ends_at.map(|ends_at| ends_at.to_rfc3339()),
+        });
+        self.ha_client
+            .publish(&json_attributes_topic, QoS::AtMostOnce, true, attributes.to_string())
+            .await?;
+
+        let Some(remaining) = countdown.remaining_seconds() else {
+            // HA shows "None" as unknown
+            self.ha_client.publish(&state_topic, QoS::AtMostOnce, true, "None").await?;
+            return Ok(());
+        };
+        self.ha_client
+            .publish(&state_topic, QoS::AtMostOnce, true, remaining.to_string())
+            .await?;
+
+        if started {
+            let ha_client = self.ha_client.clone();
+            let area_number = area.area_number;
+            let session = processor_state.read().await.session.clone();
+            let ticking = async move {
+                let mut tick = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    tick.tick().await;
+                    let still_running = processor_state
+                        .read()
+                        .await
+                        .area_countdowns
+                        .get(&area_number)
+                        .is_some_and(|current| current.started_at == countdown.started_at);
+                    if !still_running {
+                        break;
+                    }
+                    let remaining = countdown.remaining_seconds().unwrap_or(0);
+                    if let Err(e) = ha_client
+                        .publish(&state_topic, QoS::AtMostOnce, true, remaining.to_string())
+                        .await
+                    {
+                        warn!("Error publishing countdown to {}: {:?}", state_topic, e);
+                        break;
+                    }
+                    if remaining == 0 {
+                        break;
+                    }
+                }
+            };
+            // Stops with the device's connection, or a reconnect would keep ticking a stale countdown
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = ProcessorState::session_ended(session) => {}
+                    _ = ticking => {}
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Number of open zones in the area, with the open and all zones of the area as attributes
+    pub async fn handle_zones_open_sensor(
+        &self,
+        device: &UserDevice,
+        area: &AreaObject,
+        area_zones: &[ZoneObject],
+        open_zones: &[ZoneObject],
+        availability: Vec<Availability>,
+        should_publish: bool,
+    ) -> anyhow::Result<()> {
+        let unique_id = format!("olarm_{}_area_{}_zones_open", self.topics.device_key(&device.id), area.area_number);
+        let state_topic = self.topics.area(&device.id, area.area_number, "zones_open/state");
+        let json_attributes_topic = self.topics.area(&device.id, area.area_number, "zones_open/attributes");
+
+        if should_publish {
+            let discovery_object = SensorDiscoveryPayload {
+                device: area.ha_device(device, &self.topics),
+                name: self.topics.area_entity_name(device, area.area_number, &area.name, "Zones Open"),
+                unique_id: unique_id.clone(),
+                state_topic: state_topic.clone(),
+                device_class: None,
+                state_class: Some("measurement".to_string()),
+                unit_of_measurement: None,
+                icon: Some("mdi:door-open".to_string()),
+                entity_category: None,
+                json_attributes_topic: Some(json_attributes_topic.clone()),
+                availability: Some(availability),
+                availability_mode: None, //defaults to "latest"
+            };
+            self.discovery
+                .publish(
+                    &self.ha_client,
+                    &self.topics.device_key(&device.id),
+                    &self.topics.discovery("sensor", &unique_id),
+                    serde_json::to_string(&discovery_object)?,
+                )
+                .await?;
+        }
+
+        let attributes = serde_json::json!({
+            "open_zones": open_zones.iter().map(|zone| zone.name.clone()).collect::<Vec<_>>(),
+            "open_zone_numbers": open_zones.iter().map(|zone| zone.zone_number).collect::<Vec<_>>(),
+            "zones": area_zones.iter().map(|zone| zone.name.clone()).collect::<Vec<_>>(),
+            "zone_numbers": area_zones.iter().map(|zone| zone.zone_number).collect::<Vec<_>>(),
+        });
+        self.ha_client
+            .publish(&json_attributes_topic, QoS::AtMostOnce, true, attributes.to_string())
+            .await?;
+        self.ha_client
+            .publish(&state_topic, QoS::AtMostOnce, true, open_zones.len().to_string())
+            .await?;
+        Ok(())
+    }
+
+    /// Ready-to-arm, not-ready, fire and panic sensors for an area
+    pub async fn handle_area_sensors(
+        &self,
+        device: &UserDevice,
+        area: &AreaObject,
+        open_zones: &[ZoneObject],
+        availability: Vec<Availability>,
+        should_publish: bool,
+    ) -> anyhow::Result<()> {
+        let not_ready = area.raw_state == "notready";
+        let ready = ready_to_arm(&area.raw_state, !open_zones.is_empty());
+        let open_zone_attributes = serde_json::json!({
+            "open_zones": open_zones.iter().map(|zone| zone.name.clone()).collect::<Vec<_>>(),
+            "open_zone_numbers": open_zones.iter().map(|zone| zone.zone_number).collect::<Vec<_>>(),
+        });
+        let sensors = [
+            ("ready", "Ready to Arm", None, ready, Some(open_zone_attributes.clone())),
+            ("not_ready", "Not Ready", Some("problem"), not_ready, Some(open_zone_attributes)),
+            ("fire", "Fire", Some("smoke"), area.raw_state == "fire", None),
+            ("emergency", "Panic", Some("safety"), area.raw_state == "emergency", None),
+        ];
+        for (key, label, device_class, is_on, attributes) in sensors {
+            self.handle_area_binary_sensor(
+                device,
+                area,
+                key,
+                label,
+                device_class,
+                is_on,
+                attributes,
+                Some(availability.clone()),
+                should_publish,
+            )
+            .await?;
+        }
+        Ok(())
+    }
 }
 impl<T:OlarmApiTrait + Sync + Send + Clone> MqttDeviceResponseProcessor for PanelProcessor<T> {
     async fn handle(
@@ -61,30 +418,54 @@ impl<T:OlarmApiTrait + Sync + Send + Clone> MqttDeviceResponseProcessor for Pane
         msg: MqttDeviceResponse,
         processor_state: Arc<RwLock<ProcessorState>>,
     ) -> anyhow::Result<()> {
-        let (device_profile, device) = {
+        let (device_profile, device, device_config, zones) = {
             let read_lock = processor_state.read().await;
-            (read_lock.device_profile.clone(), read_lock.device.clone())
+            let zones = ZonesProcessor::get_zones(
+                &msg, &read_lock.device_profile, &read_lock.zone_types, &read_lock.device_config,
+            );
+            (read_lock.device_profile.clone(), read_lock.device.clone(), read_lock.device_config.clone(), zones)
         };
         let device_id = device.id.clone();
+        let device_key = self.topics.device_key(&device_id);
 
-        for area in Self::get_areas(&msg, &device_profile) {
-            let discovery_topic = format!(
-                "homeassistant/alarm_control_panel/{}_area_{}/config", device_id, area.area_number
+        for mut area in Self::get_areas(&msg, &device_profile) {
+            let discovery_topic = self.topics.discovery(
+                "alarm_control_panel", &format!("{}_area_{}", device_key, area.area_number)
             );
-            let unique_id = format!("olarm_{}_area_{}", device_id, area.area_number);
+            let unique_id = format!("olarm_{}_area_{}", device_key, area.area_number);
             
-            let state_topic = format!("olarm/device/{}/area/{}/state", device_id, area.area_number);
-            let control_topic = format!("olarm/device/{}/area/{}/set", device_id, area.area_number);
-            let json_attributes_topic = format!("olarm/device/{}/area/{}/attributes", device_id, area.area_number);
-            let global_availability_topic = format!("olarm/device/{}/availability", device_id);
-            let device_availability_topic = format!("olarm/device/{}/area/{}/availability", device_id, area.area_number);
+            let state_topic = self.topics.area(&device_id, area.area_number, "state");
+            let control_topic = self.topics.area(&device_id, area.area_number, "set");
+            let json_attributes_topic = self.topics.area(&device_id, area.area_number, "attributes");
+            let global_availability_topic = self.topics.device_availability(&device_id);
+            let device_availability_topic = self.topics.area(&device_id, area.area_number, "availability");
+            let arm_bypass_unique_id = format!("olarm_{}_area_{}_arm_bypass", device_key, area.area_number);
+            let arm_bypass_discovery_topic = self.topics.discovery("button", &arm_bypass_unique_id);
+            let arm_bypass_command_topic = self.topics.area(&device_id, area.area_number, "arm_bypass/set");
+            let result_topic = self.topics.area(&device_id, area.area_number, "result");
             
+            let availability = vec![
+                Availability{
+                    payload_available: Some(AvailabilityState::Online.as_serde_value()),
+                    payload_not_available: Some(AvailabilityState::Offline.as_serde_value()),
+                    topic: global_availability_topic.to_string(),
+                    value_template: None,
+                },
+                Availability{
+                    payload_available: Some(AvailabilityState::Online.as_serde_value()),
+                    payload_not_available: Some(AvailabilityState::Offline.as_serde_value()),
+                    topic: device_availability_topic.to_string(),
+                    value_template: None,
+                }
+            ];
+
             // Atomically check-and-insert without holding a lock across .await
             let should_publish = processor_state.write().await.published_discovery.insert(unique_id.clone());
 
             if should_publish {
                 let discovery_object = AlarmControlPanelDiscoveryPayload {
-                    name: format!("Olarm {} Area {}", device.name, area.area_number),
+                    device: area.ha_device(&device, &self.topics),
+                    name: self.topics.area_name(&device, area.area_number, &area.name),
                     unique_id: unique_id.clone(),
                     state_topic: state_topic.clone(),
                     command_topic: control_topic.clone(),
@@ -92,44 +473,68 @@ impl<T:OlarmApiTrait + Sync + Send + Clone> MqttDeviceResponseProcessor for Pane
                     payload_arm_home: ActionCmd::AreaStay.to_string(),
                     payload_arm_night: ActionCmd::AreaSleep.to_string(),
                     payload_disarm: ActionCmd::AreaDisarm.to_string(),
+                    payload_arm_custom_bypass: Some(ARM_CUSTOM_BYPASS_PAYLOAD.to_string()),
                     code: None,
                     code_arm_required: Some(false),
                     code_disarm_required: Some(false),
                     code_trigger_required: Some(false),
                     command_template: None,
                     json_attributes_topic: Some(json_attributes_topic.clone()),
-                    availability: Some(vec![
-                        Availability{
-                            payload_available: Some(AvailabilityState::Online.as_serde_value()),
-                            payload_not_available: Some(AvailabilityState::Offline.as_serde_value()),
-                            topic: global_availability_topic.to_string(),
-                            value_template: None,
-                        },
-                        Availability{
-                            payload_available: Some(AvailabilityState::Online.as_serde_value()),
-                            payload_not_available: Some(AvailabilityState::Offline.as_serde_value()),
-                            topic: device_availability_topic.to_string(),
-                            value_template: None,
-                        }
-                    ]),
+                    availability: Some(availability.clone()),
                     availability_mode: None, //defaults to "latest"
                     supported_features: Some(vec![
                         AlarmFeature::ArmAway,
                         AlarmFeature::ArmHome,
-                        AlarmFeature::ArmNight
+                        AlarmFeature::ArmNight,
+                        AlarmFeature::ArmCustomBypass
                     ]),
                 };
 
-                self.ha_client
-                    .publish(&discovery_topic, QoS::AtLeastOnce, true, serde_json::to_string(&discovery_object)?)
+                self.discovery
+                    .publish(&self.ha_client, &device_key, &discovery_topic, serde_json::to_string(&discovery_object)?)
                     .await?;
 
                 self.ha_client
                     .subscribe(&control_topic, QoS::AtLeastOnce)
                     .await?;
 
+                let arm_bypass_object = ButtonDiscoveryPayload {
+                    device: area.ha_device(&device, &self.topics),
+                    name: self.topics.area_entity_name(&device, area.area_number, &area.name, "Bypass Open Zones and Arm"),
+                    unique_id: arm_bypass_unique_id.clone(),
+                    command_topic: arm_bypass_command_topic.clone(),
+                    payload_press: Some(ArmBypassRequest::new(ActionCmd::AreaArm).to_payload()),
+                    icon: Some("mdi:shield-lock-open".to_string()),
+                    entity_category: None,
+                    json_attributes_topic: Some(result_topic.clone()),
+                    availability: Some(availability.clone()),
+                    availability_mode: None, //defaults to "latest"
+                };
+
+                self.discovery
+                    .publish(&self.ha_client, &device_key, &arm_bypass_discovery_topic, serde_json::to_string(&arm_bypass_object)?)
+                    .await?;
+
+                self.ha_client
+                    .subscribe(&arm_bypass_command_topic, QoS::AtLeastOnce)
+                    .await?;
+
             }
 
+            let open_zones = ZonesProcessor::get_open_zones(&zones, &device_config, area.area_number);
+            let countdown = Self::track_countdown(&processor_state, &device_config, &mut area, !open_zones.is_empty()).await;
+            self.handle_area_sensors(&device, &area, &open_zones, availability.clone(), should_publish)
+                .await?;
+            let area_zones = zones
+                .iter()
+                .filter(|zone| device_config.zone_in_area(zone.zone_number, area.area_number))
+                .cloned()
+                .collect::<Vec<_>>();
+            self.handle_zones_open_sensor(&device, &area, &area_zones, &open_zones, availability.clone(), should_publish)
+                .await?;
+            self.handle_countdown_sensor(&device, &area, countdown, availability.clone(), should_publish, processor_state.clone())
+                .await?;
+
             if let Some(area_state) = &area.state {
                 self.ha_client
                     .publish(&state_topic, QoS::AtMostOnce, true, area_state.to_string())
@@ -138,8 +543,16 @@ impl<T:OlarmApiTrait + Sync + Send + Clone> MqttDeviceResponseProcessor for Pane
 
             self.ha_client.publish(&device_availability_topic, QoS::AtLeastOnce, true, AvailabilityState::Online.as_serde_value()).await?;
 
+            let mut attributes: HashMap<String, String> = HashMap::new();
+            attributes.insert("areaState".to_string(), area.raw_state.clone());
+            if let Some(detail) = &area.detail {
+                attributes.insert("areaDetail".to_string(), detail.clone());
+            }
+            if let Some(last_changed) = area.last_changed {
+                attributes.insert("areaStamp".to_string(), last_changed.to_rfc3339());
+            }
+
             if let Ok(device_actions) = self.olarm_client.get_actions(&device.id).await {
-                let mut attributes: HashMap<String, String> = HashMap::new();
                 let mut user_fullname = "No User".to_string();
                 let mut action_created: i64 = 0;
                 let mut action_cmd: Option<String> = None;
@@ -169,11 +582,69 @@ impl<T:OlarmApiTrait + Sync + Send + Clone> MqttDeviceResponseProcessor for Pane
                 if let Some(cmd) = action_cmd {
                     attributes.insert("actionCmd".to_string(), cmd.to_string());
                 }
-
-                self.ha_client.publish(&json_attributes_topic, QoS::AtMostOnce, true, serde_json::to_string(&attributes).unwrap()).await?;
             }
+
+            self.ha_client.publish(&json_attributes_topic, QoS::AtMostOnce, true, serde_json::to_string(&attributes).unwrap()).await?;
            
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn countdown_after_an_armed_state_is_entry_delay() {
+        for previous_state in ["arm", "stay", "sleep"] {
+            assert_eq!(CountdownKind::infer(Some(previous_state), false), CountdownKind::Entry);
+            assert_eq!(CountdownKind::infer(Some(previous_state), true), CountdownKind::Entry);
+        }
+    }
+
+    #[test]
+    fn countdown_after_a_disarmed_state_is_exit_delay() {
+        for previous_state in ["disarm", "notready"] {
+            // Zones opened on the way out don't make it an entry delay
+            assert_eq!(CountdownKind::infer(Some(previous_state), true), CountdownKind::Exit);
+            assert_eq!(CountdownKind::infer(Some(previous_state), false), CountdownKind::Exit);
+        }
+    }
+
+    #[test]
+    fn countdown_without_a_known_previous_state_follows_open_zones() {
+        for previous_state in [None, Some("countdown"), Some("alarm")] {
+            assert_eq!(CountdownKind::infer(previous_state, true), CountdownKind::Entry);
+            assert_eq!(CountdownKind::infer(previous_state, false), CountdownKind::Exit);
+        }
+    }
+
+    #[test]
+    fn ready_to_arm_only_when_disarmed_without_open_zones() {
+        assert!(ready_to_arm("disarm", false));
+        assert!(!ready_to_arm("disarm", true));
+        assert!(!ready_to_arm("notready", true));
+    }
+
+    #[test]
+    fn armed_areas_are_not_ready_to_arm() {
+        for raw_state in ["arm", "stay", "sleep", "countdown"] {
+            assert!(!ready_to_arm(raw_state, false), "{}", raw_state);
+        }
+    }
+
+    #[test]
+    fn triggered_areas_are_not_ready_to_arm() {
+        for raw_state in ["alarm", "fire", "emergency"] {
+            assert!(!ready_to_arm(raw_state, false), "{}", raw_state);
+            assert!(!ready_to_arm(raw_state, true), "{}", raw_state);
+        }
+    }
+
+    #[test]
+    fn countdown_kinds_map_to_arming_and_pending() {
+        assert_eq!(CountdownKind::Exit.alarm_state().as_serde_value(), "arming");
+        assert_eq!(CountdownKind::Entry.alarm_state().as_serde_value(), "pending");
+    }
+}
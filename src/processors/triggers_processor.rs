@@ -0,0 +1,141 @@
+use crate::home_assistant::availability::{Availability, AvailabilityState};
+use crate::home_assistant::device::Device;
+use crate::home_assistant::discovery_registry::DiscoveryRegistry;
+use crate::home_assistant::sensor::SensorDiscoveryPayload;
+use crate::olarm_api::models::response::mqtt_device_response::MqttDeviceResponse;
+use crate::processors::{MqttDeviceResponseProcessor, ProcessorState};
+use crate::topics::TopicBuilder;
+use rumqttc::QoS;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Publishes the device's Olarm notification triggers as diagnostic sensors.
+/// Each sensor counts the areas or zones with the trigger set and lists the raw settings.
+#[derive(Clone)]
+pub struct TriggersProcessor {
+    pub ha_client: rumqttc::AsyncClient,
+    pub topics: Arc<TopicBuilder>,
+    pub discovery: Arc<DiscoveryRegistry>,
+}
+
+impl TriggersProcessor {
+    fn label(labels: &[String], index: usize, fallback: &str) -> String {
+        labels
+            .get(index)
+            .filter(|label| !label.is_empty())
+            .cloned()
+            .unwrap_or_else(|| format!("{} {}", fallback, index + 1))
+    }
+
+    /// Settings keyed by area/zone label, and how many of them are set
+    fn summarize(settings: Vec<(String, Value)>) -> (usize, Value) {
+        let is_set = |value: &Value| match value {
+            Value::Number(number) => number.as_i64() != Some(0),
+            Value::Array(values) => values.iter().any(|value| value.as_i64() != Some(0)),
+            _ => false,
+        };
+        let count = settings.iter().filter(|(_, value)| is_set(value)).count();
+        (count, Value::Object(settings.into_iter().collect::<Map<_, _>>()))
+    }
+
+    pub async fn publish(&self, processor_state: &Arc<RwLock<ProcessorState>>) -> anyhow::Result<()> {
+        let (device, device_profile) = {
+            let state = processor_state.read().await;
+            (state.device.clone(), state.device_profile.clone())
+        };
+        let triggers = &device.triggers;
+
+        let area_label = |index| Self::label(&device_profile.areas_labels, index, "Area");
+        let zone_label = |index| Self::label(&device_profile.zones_labels, index, "Zone");
+        let sensors = [
+            (
+                "arming_reminders",
+                "Arming Reminders",
+                "mdi:bell-ring-outline",
+                Self::summarize(
+                    triggers.areas_remind.iter().enumerate()
+                        .map(|(index, remind)| (area_label(index), Value::from(remind.clone())))
+                        .collect(),
+                ),
+            ),
+            (
+                "zone_idle_alerts",
+                "Zone Idle Alerts",
+                "mdi:sleep",
+                Self::summarize(
+                    triggers.zones_idle.iter().enumerate()
+                        .map(|(index, idle)| (zone_label(index), Value::from(*idle)))
+                        .collect(),
+                ),
+            ),
+            (
+                "zone_watches",
+                "Zone Watches",
+                "mdi:eye-outline",
+                Self::summarize(
+                    triggers.zones_watch.iter().enumerate()
+                        .map(|(index, watch)| (zone_label(index), Value::from(watch.clone())))
+                        .collect(),
+                ),
+            ),
+        ];
+
+        for (key, label, icon, (count, settings)) in sensors {
+            let unique_id = format!("olarm_{}_{}", self.topics.device_key(&device.id), key);
+            let state_topic = self.topics.device(&device.id, &format!("triggers/{}/state", key));
+            let json_attributes_topic = self.topics.device(&device.id, &format!("triggers/{}/attributes", key));
+            let should_publish = processor_state
+                .write()
+                .await
+                .published_discovery
+                .insert(unique_id.clone());
+            if should_publish {
+                let discovery_object = SensorDiscoveryPayload {
+                    device: Device::from_user_device(&device, &self.topics),
+                    name: format!("{} {}", self.topics.device_name(&device), label),
+                    unique_id: unique_id.clone(),
+                    state_topic: state_topic.clone(),
+                    device_class: None,
+                    state_class: None,
+                    unit_of_measurement: None,
+                    icon: Some(icon.to_string()),
+                    entity_category: Some("diagnostic".to_string()),
+                    json_attributes_topic: Some(json_attributes_topic.clone()),
+                    availability: Some(vec![Availability {
+                        payload_available: Some(AvailabilityState::Online.as_serde_value()),
+                        payload_not_available: Some(AvailabilityState::Offline.as_serde_value()),
+                        topic: self.topics.device_availability(&device.id),
+                        value_template: None,
+                    }]),
+                    availability_mode: None, //defaults to "latest"
+                };
+                self.discovery
+                    .publish(
+                        &self.ha_client,
+                        &self.topics.device_key(&device.id),
+                        &self.topics.discovery("sensor", &unique_id),
+                        serde_json::to_string(&discovery_object)?,
+                    )
+                    .await?;
+            }
+            self.ha_client
+                .publish(&json_attributes_topic, QoS::AtMostOnce, true, settings.to_string())
+                .await?;
+            self.ha_client
+                .publish(&state_topic, QoS::AtMostOnce, true, count.to_string())
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl MqttDeviceResponseProcessor for TriggersProcessor {
+    async fn handle(
+        &self,
+        _msg: MqttDeviceResponse,
+        processor_state: Arc<RwLock<ProcessorState>>,
+    ) -> anyhow::Result<()> {
+        self.publish(&processor_state).await
+    }
+}
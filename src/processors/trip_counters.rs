@@ -0,0 +1,114 @@
+use crate::persist;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::warn;
+
+/// How often a zone was tripped, i.e. went from closed to active
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ZoneTrips {
+    pub total: u64,
+    pub today: u64,
+    /// Local date `today` counts for
+    pub day: Option<NaiveDate>,
+    pub last_tripped: Option<DateTime<Utc>>,
+}
+
+impl ZoneTrips {
+    /// Trips on the current local date
+    pub fn today(&self) -> u64 {
+        if self.day == Some(Local::now().date_naive()) {
+            self.today
+        } else {
+            0
+        }
+    }
+}
+
+/// Trip counters of one device's zones, keyed by zone number. Persisted so totals survive restarts.
+pub struct TripCounters {
+    path: PathBuf,
+    zones: Mutex<HashMap<usize, ZoneTrips>>,
+    /// Held while saving, so snapshots reach the file in the order they were taken
+    save_lock: tokio::sync::Mutex<()>,
+}
+
+impl TripCounters {
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let zones = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable trip counters {:?}: {:?}", path, e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            path,
+            zones: Mutex::new(zones),
+            save_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn get(&self, zone_number: usize) -> ZoneTrips {
+        self.zones
+            .lock()
+            .unwrap()
+            .get(&zone_number)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub async fn record_trip(&self, zone_number: usize, tripped_at: DateTime<Utc>) -> ZoneTrips {
+        let today = Local::now().date_naive();
+        let trips = {
+            let mut zones = self.zones.lock().unwrap();
+            let trips = zones.entry(zone_number).or_default();
+            if trips.day != Some(today) {
+                trips.day = Some(today);
+                trips.today = 0;
+            }
+            trips.today += 1;
+            trips.total += 1;
+            trips.last_tripped = Some(tripped_at);
+            trips.clone()
+        };
+        self.save().await;
+        trips
+    }
+
+    async fn save(&self) {
+        let _saving = self.save_lock.lock().await;
+        let snapshot = self.zones.lock().unwrap().clone();
+        if let Err(e) = persist::write_json(&self.path, &snapshot).await {
+            warn!("Failed to save trip counters {:?}: {:?}", self.path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trips_today_reset_on_a_new_day() {
+        let today = Local::now().date_naive();
+        let trips = ZoneTrips {
+            total: 7,
+            today: 3,
+            day: Some(today),
+            last_tripped: None,
+        };
+        assert_eq!(trips.today(), 3);
+
+        let yesterday = ZoneTrips {
+            day: today.pred_opt(),
+            ..trips
+        };
+        assert_eq!(yesterday.today(), 0);
+        assert_eq!(yesterday.total, 7);
+    }
+}
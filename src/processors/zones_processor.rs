@@ -1,30 +1,51 @@
-﻿use crate::ZoneObject;
-use crate::home_assistant::binary_sensor::{BinarySensorDiscoveryPayload, Device};
+﻿use crate::{ZoneArea, ZoneObject};
+use crate::config::{DeviceConfig, ZoneTypeConfig};
+use crate::home_assistant::discovery_registry::DiscoveryRegistry;
+use crate::topics::TopicBuilder;
+use crate::home_assistant::binary_sensor::BinarySensorDiscoveryPayload;
+use crate::home_assistant::sensor::SensorDiscoveryPayload;
 use crate::home_assistant::switch::SwitchDiscoveryPayload;
 use crate::olarm_api::models::device_profile::DeviceProfile;
+use crate::olarm_api::models::zone_status::ZoneStatus;
 use crate::olarm_api::models::response::mqtt_device_response::MqttDeviceResponse;
 use crate::olarm_api::models::response::user_response::UserDevice;
+use crate::processors::trip_counters::{TripCounters, ZoneTrips};
 use crate::processors::{MqttDeviceResponseProcessor, ProcessorState};
-use chrono::DateTime;
+use chrono::{DateTime, Utc};
 use rumqttc::QoS;
 use std::cmp::min;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::join;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, watch};
 use tracing::{error, trace};
 use crate::home_assistant::availability::{Availability, AvailabilityState};
 use crate::home_assistant::models::requests::zone_bypass::ZoneBypassRequest;
 
+/// Zone state last published to HA, used to debounce flapping zones
+#[derive(Debug, Clone)]
+pub struct DebouncedZone {
+    pub active: bool,
+    pub since: DateTime<Utc>,
+    /// A re-check is scheduled for when the minimum duration has passed
+    pub recheck_scheduled: bool,
+}
+
 #[derive(Clone)]
 pub struct ZonesProcessor {
     pub ha_client: rumqttc::AsyncClient,
+    pub topics: Arc<TopicBuilder>,
+    pub discovery: Arc<DiscoveryRegistry>,
+    pub trip_counters: Arc<TripCounters>,
 }
 
 impl ZonesProcessor {
     pub fn get_zones(
         payload: &MqttDeviceResponse,
         device_profile: &DeviceProfile,
+        zone_types: &HashMap<String, ZoneTypeConfig>,
+        device_config: &DeviceConfig,
     ) -> Vec<ZoneObject> {
         let zone_limit = device_profile.zones_limit as usize;
 
@@ -40,34 +61,27 @@ impl ZonesProcessor {
 
         let mut zones: Vec<ZoneObject> = Vec::with_capacity(safe_count + 2);
         for i in 0..safe_count {
-            let mut bypass_state = "off";
-            let zone_state = match payload.data.zones[i].to_ascii_lowercase().as_str() {
-                "a" => "on",
-                "b" => {
-                    bypass_state = "on";
-                    "off"
-                }
-                _ => "off",
-            };
-            
+            let status = ZoneStatus::from(payload.data.zones[i].as_str());
+            let zone_state = if status.is_active() { "on" } else { "off" };
+            let bypass_state = if status.is_bypassed() { "on" } else { "off" };
+
             let last_changed_dt_opt =
                 match payload.data.zones_stamp[i] {
                     None => None,
                     Some(x) => { DateTime::from_timestamp_millis(x as i64) }
                 };
-            let (zone_type, zone_friendly_type) = match device_profile.zones_types[i] {
-                0 => ("motion", "Motion Sensor"),
-                10 => ("door", "Door Sensor"),
-                11 => ("window", "Window Sensor"),
-                20 => ("motion", "Motion Sensor"),
-                21 => ("motion", "Motion Sensor"),
-                90 => ("problem", "Sensor Disabled"),
-                50 => ("safety", "Panic Button"),
-                51 => ("safety", "Panic Button"),
-                1000 => ("plug", "Device Power Plug Status"),
-                1001 => ("power", "Battery Powered"),
-                _ => ("motion", "Motion Sensor"),
-            };
+            let type_code = device_profile.zones_types[i];
+            let (default_type, default_friendly_type) = Self::default_zone_type(type_code);
+            let type_config = zone_types.get(&type_code.to_string());
+            let zone_override = device_config.zone_override(i + 1);
+
+            let zone_type = zone_override
+                .and_then(|o| o.device_class.clone())
+                .or_else(|| type_config.and_then(|c| c.device_class.clone()))
+                .unwrap_or_else(|| default_type.to_string());
+            let zone_friendly_type = type_config
+                .and_then(|c| c.name.clone())
+                .unwrap_or_else(|| default_friendly_type.to_string());
             let mut zone_attributes: HashMap<String, String> = HashMap::with_capacity(3);
             zone_attributes.insert("zone_number".to_string(), i.to_string());
             if let Some(last_changed_dt) = last_changed_dt_opt {
@@ -76,55 +90,130 @@ impl ZonesProcessor {
                     last_changed_dt.to_rfc3339(),
                 );
             }
-            zone_attributes.insert("zone_type".to_string(), zone_friendly_type.to_string());
+            zone_attributes.insert("zone_type".to_string(), zone_friendly_type);
+            zone_attributes.insert("zone_type_code".to_string(), type_code.to_string());
+            zone_attributes.insert("zone_status".to_string(), status.code().to_string());
+            let area = Self::zone_area(device_profile, device_config, i + 1);
+            if let Some(area) = &area {
+                zone_attributes.insert("area_number".to_string(), area.number.to_string());
+                zone_attributes.insert("area".to_string(), area.label.clone());
+            }
 
             let zone = ZoneObject {
-                name: device_profile.zones_labels[i].to_string(),
+                name: zone_override
+                    .and_then(|o| o.name.clone())
+                    .unwrap_or_else(|| device_profile.zones_labels[i].to_string()),
                 state: zone_state.to_string(),
                 last_changed: last_changed_dt_opt,
-                r#type: zone_type.to_string(),
+                r#type: zone_type,
                 zone_number: i + 1, // Starts at 1, not 0
                 attributes: Some(zone_attributes),
                 bypass_state: bypass_state.to_string(),
+                icon: zone_override.and_then(|o| o.icon.clone()),
+                off_delay: zone_override
+                    .and_then(|o| o.off_delay)
+                    .or_else(|| type_config.and_then(|c| c.off_delay)),
+                min_on_seconds: zone_override
+                    .and_then(|o| o.min_on_seconds)
+                    .or_else(|| type_config.and_then(|c| c.min_on_seconds))
+                    .unwrap_or(0),
+                min_off_seconds: zone_override
+                    .and_then(|o| o.min_off_seconds)
+                    .or_else(|| type_config.and_then(|c| c.min_off_seconds))
+                    .unwrap_or(0),
+                hidden: zone_override.is_some_and(|o| o.hide),
+                status,
+                area,
             };
             zones.push(zone);
         }
         zones
     }
 
+    /// Area of a zone from the configured mapping. Single-area panels need no mapping.
+    fn zone_area(
+        device_profile: &DeviceProfile,
+        device_config: &DeviceConfig,
+        zone_number: usize,
+    ) -> Option<ZoneArea> {
+        let area_number = device_config
+            .zone_area(zone_number)
+            .or((device_profile.areas_limit == 1).then_some(1))?;
+        let label = device_profile
+            .areas_labels
+            .get(area_number - 1)
+            .filter(|label| !label.is_empty())
+            .cloned()
+            .unwrap_or_else(|| format!("Area {}", area_number));
+        Some(ZoneArea {
+            number: area_number,
+            label,
+            own_device: device_profile.areas_limit > 1,
+        })
+    }
+
+    /// Built-in HA device class and friendly name of an Olarm zone type code
+    fn default_zone_type(type_code: i64) -> (&'static str, &'static str) {
+        match type_code {
+            0 => ("motion", "Motion Sensor"),
+            10 => ("door", "Door Sensor"),
+            11 => ("window", "Window Sensor"),
+            20 => ("motion", "Motion Sensor"),
+            21 => ("motion", "Motion Sensor"),
+            90 => ("problem", "Sensor Disabled"),
+            50 => ("safety", "Panic Button"),
+            51 => ("safety", "Panic Button"),
+            1000 => ("plug", "Device Power Plug Status"),
+            1001 => ("power", "Battery Powered"),
+            _ => ("motion", "Motion Sensor"),
+        }
+    }
+
+    /// Zones in the area that are active and not bypassed
+    pub fn get_open_zones(
+        zones: &[ZoneObject],
+        device_config: &DeviceConfig,
+        area_number: usize,
+    ) -> Vec<ZoneObject> {
+        zones
+            .iter()
+            .filter(|zone| {
+                zone.state == "on"
+                    && zone.bypass_state != "on"
+                    && device_config.zone_in_area(zone.zone_number, area_number)
+            })
+            .cloned()
+            .collect()
+    }
+
     pub async fn handle_binary_sensor(
         &self,
         device: &UserDevice,
         unique_id: &str,
         zone: &ZoneObject,
         should_publish: bool,
+        processor_state: &Arc<RwLock<ProcessorState>>,
     ) -> anyhow::Result<()> {
         let device_id = device.id.clone();
 
-        let discovery_topic = format!("homeassistant/binary_sensor/{}/config", unique_id);
-        let state_topic = format!("olarm/device/{}/zone/{}/state", device_id, zone.zone_number);
-        let json_attributes_topic = format!(
-            "olarm/device/{}/zone/{}/attributes",
-            device_id, zone.zone_number
-        );
-        let global_availability_topic = format!("olarm/device/{}/availability", device_id);
-        let device_availability_topic = format!("olarm/device/{}/zone/{}/availability", device_id, zone.zone_number);
+        let discovery_topic = self.topics.discovery("binary_sensor", unique_id);
+        let state_topic = self.topics.zone(&device_id, zone.zone_number, "state");
+        let json_attributes_topic = self.topics.zone(&device_id, zone.zone_number, "attributes");
+        let global_availability_topic = self.topics.device_availability(&device_id);
+        let device_availability_topic = self.topics.zone(&device_id, zone.zone_number, "availability");
 
         if should_publish {
             let discovery_object = BinarySensorDiscoveryPayload {
-                device: Device {
-                    identifiers: vec![device.imei.clone(), device.id.clone()],
-                    manufacturer: "Daniel van Schoor".to_string(),
-                    name: format!("Olarm Sensors({})", &device.name),
-                    model: device.alarm_type.clone(),
-                },
-                name: zone.name.clone(),
+                device: zone.ha_device(device, &self.topics),
+                name: self.topics.zone_name(device, zone.zone_number, &zone.name),
                 state_topic: state_topic.clone(),
                 unique_id: unique_id.to_string(),
                 payload_on: "on".to_string(),
                 payload_off: "off".to_string(),
-                device_class: zone.r#type.to_string(),
-                off_delay: None, //if zone.r#type == "motion" { Some(2) } else { None },
+                device_class: Some(zone.r#type.to_string()),
+                off_delay: zone.off_delay,
+                icon: zone.icon.clone(),
+                entity_category: None,
                 json_attributes_topic: Some(json_attributes_topic.clone()),
                 availability: Some(vec![
                     Availability{
@@ -145,14 +234,12 @@ impl ZonesProcessor {
             let discovery_payload = serde_json::to_string(&discovery_object)?;
             trace!("{}", discovery_payload);
 
-            self.ha_client
-                .publish(&discovery_topic, QoS::AtLeastOnce, true, discovery_payload)
+            self.discovery
+                .publish(&self.ha_client, &self.topics.device_key(&device_id), &discovery_topic, discovery_payload)
                 .await?;
         }
         match join!(
-            self.ha_client
-                .publish(&state_topic, QoS::AtMostOnce, true, zone.state.clone()),
-
+            self.publish_debounced_state(&device_id, zone, should_publish, processor_state),
             self.ha_client.publish(
                 &json_attributes_topic,
                 QoS::AtMostOnce,
@@ -161,7 +248,11 @@ impl ZonesProcessor {
             ),
             self.ha_client.publish(&device_availability_topic, QoS::AtLeastOnce, true, AvailabilityState::Online.as_serde_value())
         ) {
-            (Err(e), _, _) | (_, Err(e),_) | (_, _,Err(e)) => {
+            (Err(e), _, _) => {
+                error!("Error publishing to {}: {:?}", state_topic, e);
+                Err(e)
+            }
+            (_, Err(e),_) | (_, _,Err(e)) => {
                 error!("Error publishing to {}: {:?}", state_topic, e);
                 Err(anyhow::Error::from(e))
             }
@@ -169,6 +260,122 @@ impl ZonesProcessor {
         }
     }
 
+    /// Publishes the zone state once it has held for the zone's minimum on/off duration.
+    /// A change that comes too soon is held back and re-checked when the duration has passed.
+    pub async fn publish_debounced_state(
+        &self,
+        device_id: &str,
+        zone: &ZoneObject,
+        force_publish: bool,
+        processor_state: &Arc<RwLock<ProcessorState>>,
+    ) -> anyhow::Result<()> {
+        let (publish, recheck_after, session) = {
+            let mut state = processor_state.write().await;
+            let (publish, recheck_after) = Self::debounce(&mut state, zone, force_publish);
+            (publish, recheck_after, state.session.clone())
+        };
+        if let Some(recheck_after) = recheck_after {
+            self.schedule_recheck(device_id, zone.zone_number, recheck_after, processor_state, session);
+        }
+        if let Some(active) = publish {
+            self.publish_zone_state(device_id, zone.zone_number, active).await?;
+        }
+        Ok(())
+    }
+
+    /// Decides which state to publish for the zone, if any, and when to look at it again
+    fn debounce(
+        state: &mut ProcessorState,
+        zone: &ZoneObject,
+        force_publish: bool,
+    ) -> (Option<bool>, Option<Duration>) {
+        let active = zone.status.is_active();
+        let now = Utc::now();
+        let Some(debounced) = state.zone_debounce.get_mut(&zone.zone_number) else {
+            state.zone_debounce.insert(
+                zone.zone_number,
+                DebouncedZone { active, since: now, recheck_scheduled: false },
+            );
+            return (Some(active), None);
+        };
+        if debounced.active == active {
+            return (force_publish.then_some(active), None);
+        }
+
+        let min_seconds = if debounced.active { zone.min_on_seconds } else { zone.min_off_seconds };
+        let held = (now - debounced.since).num_milliseconds().max(0) as u64;
+        if held >= min_seconds * 1000 {
+            *debounced = DebouncedZone { active, since: now, recheck_scheduled: false };
+            (Some(active), None)
+        } else if debounced.recheck_scheduled {
+            (force_publish.then_some(debounced.active), None)
+        } else {
+            debounced.recheck_scheduled = true;
+            (
+                force_publish.then_some(debounced.active),
+                Some(Duration::from_millis(min_seconds * 1000 - held)),
+            )
+        }
+    }
+
+    fn schedule_recheck(
+        &self,
+        device_id: &str,
+        zone_number: usize,
+        mut recheck_after: Duration,
+        processor_state: &Arc<RwLock<ProcessorState>>,
+        session: watch::Receiver<()>,
+    ) {
+        let processor = self.clone();
+        let device_id = device_id.to_string();
+        let processor_state = processor_state.clone();
+        let rechecking = async move {
+            loop {
+                tokio::time::sleep(recheck_after).await;
+                // Re-evaluate against the latest status, the zone may have changed back meanwhile
+                let (publish, next_recheck) = {
+                    let mut state = processor_state.write().await;
+                    if let Some(debounced) = state.zone_debounce.get_mut(&zone_number) {
+                        debounced.recheck_scheduled = false;
+                    }
+                    let zone = state.latest_response.as_ref().and_then(|response| {
+                        Self::get_zones(response, &state.device_profile, &state.zone_types, &state.device_config)
+                            .into_iter()
+                            .find(|zone| zone.zone_number == zone_number)
+                    });
+                    match zone {
+                        Some(zone) => Self::debounce(&mut state, &zone, false),
+                        None => (None, None),
+                    }
+                };
+                if let Some(active) = publish
+                    && let Err(e) = processor.publish_zone_state(&device_id, zone_number, active).await
+                {
+                    error!("Error publishing debounced state of zone {}: {:?}", zone_number, e);
+                }
+                match next_recheck {
+                    Some(next_recheck) => recheck_after = next_recheck,
+                    None => break,
+                }
+            }
+        };
+        // A recheck left over from an ended connection would publish against a stale session
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = ProcessorState::session_ended(session) => {}
+                _ = rechecking => {}
+            }
+        });
+    }
+
+    async fn publish_zone_state(&self, device_id: &str, zone_number: usize, active: bool) -> anyhow::Result<()> {
+        let state_topic = self.topics.zone(device_id, zone_number, "state");
+        self.ha_client
+            .publish(&state_topic, QoS::AtMostOnce, true, if active { "on" } else { "off" })
+            .await?;
+        Ok(())
+    }
+
     pub async fn handle_bypass_switch(
         &self,
         device: &UserDevice,
@@ -180,31 +387,17 @@ impl ZonesProcessor {
             return Ok(());
         }
         let device_id = device.id.clone();
-        let discovery_topic = format!("homeassistant/switch/{}/config", unique_id);
-        let state_topic = format!(
-            "olarm/device/{}/zone/{}/bypass/state",
-            device_id, zone.zone_number
-        );
-        let json_attributes_topic = format!(
-            "olarm/device/{}/zone/{}/bypass/attributes",
-            device_id, zone.zone_number
-        );
-        let command_topic = format!(
-            "olarm/device/{}/zone/{}/bypass/set",
-            device_id, zone.zone_number
-        );
-        let global_availability_topic = format!("olarm/device/{}/availability", device_id);
-        let device_availability_topic = format!("olarm/device/{}/zone/{}/availability", device_id, zone.zone_number);
+        let discovery_topic = self.topics.discovery("switch", unique_id);
+        let state_topic = self.topics.zone(&device_id, zone.zone_number, "bypass/state");
+        let json_attributes_topic = self.topics.zone(&device_id, zone.zone_number, "bypass/attributes");
+        let command_topic = self.topics.zone(&device_id, zone.zone_number, "bypass/set");
+        let global_availability_topic = self.topics.device_availability(&device_id);
+        let device_availability_topic = self.topics.zone(&device_id, zone.zone_number, "availability");
 
         if should_publish {
             let discovery_object = SwitchDiscoveryPayload {
-                device: Device {
-                    identifiers: vec![device.imei.clone(), device.id.clone()],
-                    manufacturer: "Daniel van Schoor".to_string(),
-                    name: format!("Olarm Sensors({})", &device.name),
-                    model: device.alarm_type.clone(),
-                },
-                name: format!("{} Bypass ({})", zone.name, &device.name),
+                device: zone.ha_device(device, &self.topics),
+                name: self.topics.zone_bypass_name(device, zone.zone_number, &zone.name),
                 payload_off: Some(ZoneBypassRequest::new(false).to_payload()),
                 payload_on: Some(ZoneBypassRequest::new(true).to_payload()),
                 state_off: Some("off".to_string()),
@@ -228,13 +421,14 @@ impl ZonesProcessor {
                     }
                 ]),
                 availability_mode: None, //defaults to "latest"
-                optimistic: Some(true),
+                // State comes from the panel's zone status, so HA should wait for it
+                optimistic: Some(false),
             };
             let discovery_payload = serde_json::to_string(&discovery_object)?;
             trace!("{}", discovery_payload);
 
-            self.ha_client
-                .publish(&discovery_topic, QoS::AtLeastOnce, true, discovery_payload)
+            self.discovery
+                .publish(&self.ha_client, &self.topics.device_key(&device_id), &discovery_topic, discovery_payload)
                 .await?;
 
             self.ha_client
@@ -264,12 +458,211 @@ impl ZonesProcessor {
         }
     }
 
-    fn build_binary_sensor_unique_id(device_id: &str, zone_number: usize) -> String {
-        format!("{}_{}_binary", device_id, zone_number)
+    /// Tamper, trouble and disabled sensors of the zone. The codes for these are unconfirmed, so
+    /// each sensor is only created once the zone has reported it, rather than sitting at "off"
+    /// if the panel uses other codes.
+    pub async fn handle_fault_sensors(
+        &self,
+        device: &UserDevice,
+        zone: &ZoneObject,
+        processor_state: &Arc<RwLock<ProcessorState>>,
+    ) -> anyhow::Result<()> {
+        let device_key = self.topics.device_key(&device.id);
+        let faults = [
+            ("tamper", "Tamper", Some("tamper"), zone.status.is_tamper()),
+            ("trouble", "Trouble", Some("problem"), zone.status.is_trouble()),
+            ("disabled", "Disabled", None, zone.status.is_disabled()),
+        ];
+        for (key, label, device_class, is_on) in faults {
+            let unique_id = self.build_zone_entity_unique_id(&device.id, zone.zone_number, key);
+            let discovery_topic = self.topics.discovery("binary_sensor", &unique_id);
+            if !is_on && !self.discovery.is_published(&device_key, &discovery_topic) {
+                continue;
+            }
+            let should_publish = processor_state
+                .write()
+                .await
+                .published_discovery
+                .insert(unique_id);
+            self.handle_fault_sensor(device, zone, key, label, device_class, is_on, should_publish)
+                .await?;
+        }
+        Ok(())
     }
 
-    fn build_bypass_switch_unique_id(device_id: &str, zone_number: usize) -> String {
-        format!("{}_{}_bypass", device_id, zone_number)
+    /// Diagnostic binary sensor for a fault condition of the zone, e.g. tamper
+    #[allow(clippy::too_many_arguments)]
+    pub async fn handle_fault_sensor(
+        &self,
+        device: &UserDevice,
+        zone: &ZoneObject,
+        key: &str,
+        label: &str,
+        device_class: Option<&str>,
+        is_on: bool,
+        should_publish: bool,
+    ) -> anyhow::Result<()> {
+        let device_id = device.id.clone();
+        let unique_id = self.build_zone_entity_unique_id(&device_id, zone.zone_number, key);
+        let discovery_topic = self.topics.discovery("binary_sensor", &unique_id);
+        let state_topic = self.topics.zone(&device_id, zone.zone_number, &format!("{}/state", key));
+        let global_availability_topic = self.topics.device_availability(&device_id);
+        let device_availability_topic = self.topics.zone(&device_id, zone.zone_number, "availability");
+
+        if should_publish {
+            let discovery_object = BinarySensorDiscoveryPayload {
+                device: zone.ha_device(device, &self.topics),
+                name: self.topics.zone_entity_name(device, zone.zone_number, &zone.name, label),
+                state_topic: state_topic.clone(),
+                unique_id: unique_id.clone(),
+                payload_on: "on".to_string(),
+                payload_off: "off".to_string(),
+                device_class: device_class.map(str::to_string),
+                off_delay: None,
+                icon: None,
+                entity_category: Some("diagnostic".to_string()),
+                json_attributes_topic: None,
+                availability: Some(vec![
+                    Availability{
+                        payload_available: Some(AvailabilityState::Online.as_serde_value()),
+                        payload_not_available: Some(AvailabilityState::Offline.as_serde_value()),
+                        topic: global_availability_topic.to_string(),
+                        value_template: None,
+                    },
+                    Availability{
+                        payload_available: Some(AvailabilityState::Online.as_serde_value()),
+                        payload_not_available: Some(AvailabilityState::Offline.as_serde_value()),
+                        topic: device_availability_topic.to_string(),
+                        value_template: None,
+                    }
+                ]),
+                availability_mode: None, //defaults to "latest"
+            };
+            let discovery_payload = serde_json::to_string(&discovery_object)?;
+            trace!("{}", discovery_payload);
+
+            self.discovery
+                .publish(&self.ha_client, &self.topics.device_key(&device_id), &discovery_topic, discovery_payload)
+                .await?;
+        }
+        self.ha_client
+            .publish(&state_topic, QoS::AtMostOnce, true, if is_on { "on" } else { "off" })
+            .await?;
+        Ok(())
+    }
+
+    /// Last-tripped timestamp and trip counter sensors of the zone
+    pub async fn handle_trip_sensors(
+        &self,
+        device: &UserDevice,
+        zone: &ZoneObject,
+        trips: &ZoneTrips,
+        processor_state: &Arc<RwLock<ProcessorState>>,
+    ) -> anyhow::Result<()> {
+        let device_id = device.id.clone();
+        let global_availability_topic = self.topics.device_availability(&device_id);
+        let device_availability_topic = self.topics.zone(&device_id, zone.zone_number, "availability");
+        let last_tripped = trips
+            .last_tripped
+            .or_else(|| zone.last_changed.filter(|_| zone.status.is_active()));
+
+        let sensors = [
+            (
+                "last_tripped", "Last Tripped", Some("timestamp"), None, "mdi:clock-alert-outline",
+                // HA shows "None" as unknown
+                last_tripped.map(|at| at.to_rfc3339()).unwrap_or_else(|| "None".to_string()),
+            ),
+            ("trips_today", "Trips Today", None, Some("total_increasing"), "mdi:counter", trips.today().to_string()),
+            ("trips_total", "Trips Total", None, Some("total_increasing"), "mdi:counter", trips.total.to_string()),
+        ];
+        for (key, label, device_class, state_class, icon, state) in sensors {
+            let unique_id = self.build_zone_entity_unique_id(&device_id, zone.zone_number, key);
+            let state_topic = self.topics.zone(&device_id, zone.zone_number, &format!("{}/state", key));
+            let should_publish = processor_state
+                .write()
+                .await
+                .published_discovery
+                .insert(unique_id.clone());
+
+            if should_publish {
+                let discovery_object = SensorDiscoveryPayload {
+                    device: zone.ha_device(device, &self.topics),
+                    name: self.topics.zone_entity_name(device, zone.zone_number, &zone.name, label),
+                    unique_id: unique_id.clone(),
+                    state_topic: state_topic.clone(),
+                    device_class: device_class.map(str::to_string),
+                    state_class: state_class.map(str::to_string),
+                    unit_of_measurement: None,
+                    icon: Some(icon.to_string()),
+                    entity_category: None,
+                    json_attributes_topic: None,
+                    availability: Some(vec![
+                        Availability{
+                            payload_available: Some(AvailabilityState::Online.as_serde_value()),
+                            payload_not_available: Some(AvailabilityState::Offline.as_serde_value()),
+                            topic: global_availability_topic.to_string(),
+                            value_template: None,
+                        },
+                        Availability{
+                            payload_available: Some(AvailabilityState::Online.as_serde_value()),
+                            payload_not_available: Some(AvailabilityState::Offline.as_serde_value()),
+                            topic: device_availability_topic.to_string(),
+                            value_template: None,
+                        }
+                    ]),
+                    availability_mode: None, //defaults to "latest"
+                };
+                let discovery_payload = serde_json::to_string(&discovery_object)?;
+                trace!("{}", discovery_payload);
+
+                self.discovery
+                    .publish(&self.ha_client, &self.topics.device_key(&device_id), &self.topics.discovery("sensor", &unique_id), discovery_payload)
+                    .await?;
+            }
+            self.ha_client
+                .publish(&state_topic, QoS::AtMostOnce, true, state)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Republishes every zone's trips today, e.g. to drop them to 0 when the local date changes
+    /// without any zone being tripped
+    pub async fn publish_trips_today(&self, processor_state: &Arc<RwLock<ProcessorState>>) -> anyhow::Result<()> {
+        let (zones, device_id) = {
+            let state = processor_state.read().await;
+            let Some(response) = &state.latest_response else {
+                return Ok(());
+            };
+            (
+                Self::get_zones(response, &state.device_profile, &state.zone_types, &state.device_config),
+                state.device.id.clone(),
+            )
+        };
+        for zone in zones.into_iter().filter(|zone| !zone.hidden) {
+            let trips = self.trip_counters.get(zone.zone_number);
+            self.ha_client
+                .publish(
+                    self.topics.zone(&device_id, zone.zone_number, "trips_today/state"),
+                    QoS::AtMostOnce,
+                    true,
+                    trips.today().to_string(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn build_binary_sensor_unique_id(&self, device_id: &str, zone_number: usize) -> String {
+        format!("{}_{}_binary", self.topics.device_key(device_id), zone_number)
+    }
+
+    fn build_bypass_switch_unique_id(&self, device_id: &str, zone_number: usize) -> String {
+        format!("{}_{}_bypass", self.topics.device_key(device_id), zone_number)
+    }
+
+    fn build_zone_entity_unique_id(&self, device_id: &str, zone_number: usize, key: &str) -> String {
+        format!("{}_{}_{}", self.topics.device_key(device_id), zone_number, key)
     }
 }
 impl MqttDeviceResponseProcessor for ZonesProcessor {
@@ -278,19 +671,21 @@ impl MqttDeviceResponseProcessor for ZonesProcessor {
         msg: MqttDeviceResponse,
         processor_state: Arc<RwLock<ProcessorState>>,
     ) -> anyhow::Result<()> {
-        let (device_profile, device) = {
+        let (zones, device) = {
             let read_lock = processor_state.read().await;
-            (read_lock.device_profile.clone(), read_lock.device.clone())
+            (
+                Self::get_zones(&msg, &read_lock.device_profile, &read_lock.zone_types, &read_lock.device_config),
+                read_lock.device.clone(),
+            )
         };
         let device_id = device.id.clone();
-        let zones = Self::get_zones(&msg, &device_profile);
 
-        for zone in zones {
+        for zone in zones.into_iter().filter(|zone| !zone.hidden) {
             trace!("{:?}", &zone);
             let binary_unique_id =
-                Self::build_binary_sensor_unique_id(&device_id, zone.zone_number);
+                self.build_binary_sensor_unique_id(&device_id, zone.zone_number);
             let bypass_unique_id =
-                Self::build_bypass_switch_unique_id(&device_id, zone.zone_number);
+                self.build_bypass_switch_unique_id(&device_id, zone.zone_number);
 
             let (binary_publish, bypass_publish) =
                 // Atomically check-and-insert without holding a lock across .await
@@ -299,11 +694,31 @@ impl MqttDeviceResponseProcessor for ZonesProcessor {
                     (state.published_discovery.insert(binary_unique_id.clone()),
                      state.published_discovery.insert(bypass_unique_id.clone()))
                 };
+            let tripped = {
+                let mut state = processor_state.write().await;
+                let was_active = state
+                    .previous_zone_states
+                    .insert(zone.zone_number, zone.status.is_active());
+                was_active == Some(false) && zone.status.is_active()
+            };
+            let trips = if tripped {
+                self.trip_counters
+                    .record_trip(zone.zone_number, zone.last_changed.unwrap_or_else(Utc::now))
+                    .await
+            } else {
+                self.trip_counters.get(zone.zone_number)
+            };
+
+            if let Err(e) = self.handle_trip_sensors(&device, &zone, &trips, &processor_state).await {
+                error!("Error processing trip sensors of zone {}: {:?}", binary_unique_id, e);
+                return Err(e);
+            }
             match join!(
-                self.handle_binary_sensor(&device, &binary_unique_id, &zone, binary_publish),
-                self.handle_bypass_switch(&device, &bypass_unique_id, &zone, bypass_publish)
+                self.handle_binary_sensor(&device, &binary_unique_id, &zone, binary_publish, &processor_state),
+                self.handle_bypass_switch(&device, &bypass_unique_id, &zone, bypass_publish),
+                self.handle_fault_sensors(&device, &zone, &processor_state)
             ) {
-                (Err(e), _) | (_, Err(e)) => {
+                (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
                     error!("Error processing zone {}: {:?}", binary_unique_id, e);
                     return Err(e);
                 }
@@ -314,3 +729,46 @@ impl MqttDeviceResponseProcessor for ZonesProcessor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zones(codes: &[&str]) -> Vec<ZoneObject> {
+        let count = codes.len();
+        let payload: MqttDeviceResponse = serde_json::from_value(serde_json::json!({
+            "status": "ok",
+            "type": "status",
+            "data": {
+                "timestamp": 0, "cmdRecv": 0, "type": "status",
+                "areas": ["disarm"], "areasDetail": [""], "areasStamp": [0],
+                "zones": codes, "zonesStamp": vec![None::<u64>; count],
+                "pgm": [], "pgmOb": [], "power": {"AC": "1", "Batt": "1"},
+            },
+        }))
+        .unwrap();
+        let device_profile: DeviceProfile = serde_json::from_value(serde_json::json!({
+            "areasLimit": 1, "areasLabels": ["House"],
+            "zonesLimit": count, "zonesLabels": vec!["Zone"; count], "zonesTypes": vec![0; count],
+            "pgmLimit": 0, "pgmLabels": [], "pgmControl": [],
+            "ukeysLimit": 0, "ukeysLabels": [], "ukeysControl": [],
+            "pgmObLimit": 0, "pgmObLabels": [], "pgmObControl": [],
+            "doorsLimit": 0, "doorsLabels": [], "ver": 1,
+        }))
+        .unwrap();
+        ZonesProcessor::get_zones(&payload, &device_profile, &HashMap::new(), &DeviceConfig::default())
+    }
+
+    #[test]
+    fn raw_zone_status_is_kept_as_an_attribute() {
+        let zones = zones(&["a", "d", "zones_processor_test_code"]);
+        let raw_status = |zone: &ZoneObject| zone.attributes.as_ref().unwrap()["zone_status"].clone();
+        assert_eq!(raw_status(&zones[0]), "a");
+        assert_eq!(zones[0].state, "on");
+        assert_eq!(raw_status(&zones[1]), "d");
+        assert!(zones[1].status.is_disabled());
+        // Unknown codes show as closed, but the code itself still reaches HA
+        assert_eq!(raw_status(&zones[2]), "zones_processor_test_code");
+        assert_eq!(zones[2].state, "off");
+    }
+}
@@ -1,6 +1,7 @@
 ﻿use rumqttc::ClientError;
 use rumqttc::{AsyncClient, QoS};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use tokio::sync::Mutex;
 
@@ -11,6 +12,8 @@ pub struct MqttThrottledClient {
     mqtt_client: AsyncClient,
     in_progress: Arc<Mutex<()>>, // Mutex for throttling messages.
     state: Arc<Mutex<Option<oneshot::Sender<()>>>>, // tracks pending response
+    /// Whether the event loop is connected to the broker, as reported by its reader
+    connected: Arc<AtomicBool>,
 }
 
 impl MqttThrottledClient {
@@ -19,10 +22,22 @@ impl MqttThrottledClient {
             mqtt_client,
             in_progress: Arc::new(Mutex::new(())),
             state: Arc::new(Mutex::new(None)),
+            connected: Arc::new(AtomicBool::new(false)),
         }
     }
 
-    /// Publish a message and wait until `notify_response` is called
+    /// Records a `ConnAck` (true) or an event loop error (false)
+    pub fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::Relaxed);
+    }
+
+    /// Whether a publish now would reach the broker rather than wait in the client's queue
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Publish a message and wait until `notify_response` is called. A failed publish returns the
+    /// `ClientError`, so callers can tell it apart from a message that was sent but not answered.
     pub async fn publish_and_wait<S, V>(
         &self,
         topic: S,
@@ -47,7 +62,9 @@ impl MqttThrottledClient {
             .await?;
         drop(lock);
         // Wait for response notification
-        let _ = tokio::time::timeout(Duration::from_secs(10), rx).await?;
+        let _ = tokio::time::timeout(Duration::from_secs(10), rx)
+            .await
+            .map_err(|_| anyhow::anyhow!("No response from the device within 10s"))?;
         drop(in_progress);
         Ok(())
     }
@@ -0,0 +1,302 @@
+use crate::config::TopicsConfig;
+use std::collections::HashMap;
+use crate::olarm_api::models::response::user_response::UserDevice;
+
+/// Builds every MQTT topic and entity name the bridge publishes, from the `[topics]` config
+#[derive(Debug, Clone)]
+pub struct TopicBuilder {
+    config: TopicsConfig,
+    /// Olarm account namespace of device topics and unique ids
+    account: Option<String>,
+    /// Device aliases keyed by device id, used in place of the id in topics and of the name in entity names
+    aliases: HashMap<String, String>,
+}
+
+impl TopicBuilder {
+    pub fn new(config: TopicsConfig) -> Self {
+        Self {
+            config,
+            account: None,
+            aliases: HashMap::new(),
+        }
+    }
+
+    /// Builder for the devices of an Olarm account. An empty name leaves them un-namespaced.
+    pub fn for_account(config: TopicsConfig, account: &str) -> Self {
+        Self {
+            account: (!account.is_empty()).then(|| account.to_string()),
+            ..Self::new(config)
+        }
+    }
+
+    pub fn with_aliases(self, aliases: HashMap<String, String>) -> Self {
+        Self { aliases, ..self }
+    }
+
+    /// The device id a device topic level stands for, resolving aliases
+    pub fn device_id_of<'a>(&'a self, topic_level: &'a str) -> &'a str {
+        self.aliases
+            .iter()
+            .find(|(_, alias)| alias.as_str() == topic_level)
+            .map(|(device_id, _)| device_id.as_str())
+            .unwrap_or(topic_level)
+    }
+
+    /// The device's alias, or its Olarm name
+    fn device_label<'a>(&'a self, device: &'a UserDevice) -> &'a str {
+        self.aliases.get(&device.id).unwrap_or(&device.name)
+    }
+
+    /// Identifies a device across accounts: `<account>_<device_id>`, or the bare id without an account.
+    /// Used for unique ids, HA device identifiers and the discovery registry.
+    pub fn device_key(&self, device_id: &str) -> String {
+        match &self.account {
+            Some(account) => format!("{}_{}", account, device_id),
+            None => device_id.to_string(),
+        }
+    }
+
+    /// Whether an entity unique id belongs to the device with this key. Unique ids are
+    /// `olarm_<device_key>_...`, or `<device_key>_...` for zone entities.
+    pub fn is_device_unique_id(device_key: &str, unique_id: &str) -> bool {
+        [Some(unique_id), unique_id.strip_prefix("olarm_")]
+            .into_iter()
+            .flatten()
+            .any(|id| id.strip_prefix(device_key).is_some_and(|rest| rest.starts_with('_')))
+    }
+
+    /// `<base>[/<account>]`
+    fn device_base(&self) -> String {
+        match &self.account {
+            Some(account) => format!("{}/{}", self.config.base_topic, account),
+            None => self.config.base_topic.clone(),
+        }
+    }
+
+    /// `<discovery_prefix>/<component>/<object_id>/config`
+    pub fn discovery(&self, component: &str, object_id: &str) -> String {
+        format!("{}/{}/{}/config", self.config.discovery_prefix, component, object_id)
+    }
+
+    /// `<base>[/<account>]/device/<device_id or alias>/<suffix>`
+    pub fn device(&self, device_id: &str, suffix: &str) -> String {
+        let device_level = self.aliases.get(device_id).map_or(device_id, String::as_str);
+        format!("{}/device/{}/{}", self.device_base(), device_level, suffix)
+    }
+
+    pub fn device_availability(&self, device_id: &str) -> String {
+        self.device(device_id, "availability")
+    }
+
+    /// `<base>/device/<device_id>/area/<area_number>/<suffix>`
+    pub fn area(&self, device_id: &str, area_number: usize, suffix: &str) -> String {
+        self.device(device_id, &format!("area/{}/{}", area_number, suffix))
+    }
+
+    /// `<base>/device/<device_id>/zone/<zone_number>/<suffix>`
+    pub fn zone(&self, device_id: &str, zone_number: usize, suffix: &str) -> String {
+        self.device(device_id, &format!("zone/{}/{}", zone_number, suffix))
+    }
+
+    /// `<base>/device/<device_id>/pgm_ob/<pgm_number>/<suffix>`
+    pub fn pgm_ob(&self, device_id: &str, pgm_number: usize, suffix: &str) -> String {
+        self.device(device_id, &format!("pgm_ob/{}/{}", pgm_number, suffix))
+    }
+
+    /// `<base>/device/<device_id>/door/<door_number>/<suffix>`
+    pub fn door(&self, device_id: &str, door_number: usize, suffix: &str) -> String {
+        self.device(device_id, &format!("door/{}/{}", door_number, suffix))
+    }
+
+    /// `<base>/bridge/<suffix>`
+    pub fn bridge(&self, suffix: &str) -> String {
+        format!("{}/bridge/{}", self.config.base_topic, suffix)
+    }
+
+    /// Splits a topic under the base topic, and the account if any, into its remaining levels
+    pub fn strip_base<'a>(&self, topic: &'a str) -> Option<Vec<&'a str>> {
+        topic
+            .strip_prefix(self.device_base().as_str())?
+            .strip_prefix('/')
+            .map(|rest| rest.split('/').collect())
+    }
+
+    pub fn device_name(&self, device: &UserDevice) -> String {
+        render(&self.config.names.device, &[("device", self.device_label(device))])
+    }
+
+    pub fn area_name(&self, device: &UserDevice, area_number: usize, area_label: &str) -> String {
+        render(
+            &self.config.names.area,
+            &[
+                ("device", self.device_label(device)),
+                ("area", &area_number.to_string()),
+                ("area_label", area_label),
+            ],
+        )
+    }
+
+    pub fn area_entity_name(
+        &self,
+        device: &UserDevice,
+        area_number: usize,
+        area_label: &str,
+        entity: &str,
+    ) -> String {
+        render(
+            &self.config.names.area_entity,
+            &[
+                ("device", self.device_label(device)),
+                ("area", &area_number.to_string()),
+                ("area_label", area_label),
+                ("entity", entity),
+            ],
+        )
+    }
+
+    pub fn zone_name(&self, device: &UserDevice, zone_number: usize, zone_label: &str) -> String {
+        render(
+            &self.config.names.zone,
+            &[
+                ("device", self.device_label(device)),
+                ("zone", zone_label),
+                ("zone_number", &zone_number.to_string()),
+            ],
+        )
+    }
+
+    pub fn zone_bypass_name(&self, device: &UserDevice, zone_number: usize, zone_label: &str) -> String {
+        render(
+            &self.config.names.zone_bypass,
+            &[
+                ("device", self.device_label(device)),
+                ("zone", zone_label),
+                ("zone_number", &zone_number.to_string()),
+            ],
+        )
+    }
+
+    pub fn zone_entity_name(
+        &self,
+        device: &UserDevice,
+        zone_number: usize,
+        zone_label: &str,
+        entity: &str,
+    ) -> String {
+        render(
+            &self.config.names.zone_entity,
+            &[
+                ("device", self.device_label(device)),
+                ("zone", zone_label),
+                ("zone_number", &zone_number.to_string()),
+                ("entity", entity),
+            ],
+        )
+    }
+
+    /// Name of an on-board PGM or door entity. `entity` may be empty.
+    pub fn output_name(&self, device: &UserDevice, output_label: &str, entity: &str) -> String {
+        render(
+            &self.config.names.output,
+            &[("device", self.device_label(device)), ("output", output_label), ("entity", entity)],
+        )
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+}
+
+fn render(template: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |name, (key, value)| {
+            name.replace(&format!("{{{}}}", key), value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topics() -> TopicBuilder {
+        TopicBuilder::new(TopicsConfig::default())
+    }
+
+    #[test]
+    fn render_fills_every_placeholder() {
+        let name = render("{device} Area {area} {entity} ({device})", &[("device", "Home"), ("area", "2"), ("entity", "Fire")]);
+        assert_eq!(name, "Home Area 2 Fire (Home)");
+    }
+
+    #[test]
+    fn render_leaves_unknown_placeholders() {
+        assert_eq!(render("{zone} {other}", &[("zone", "Front Door")]), "Front Door {other}");
+    }
+
+    #[test]
+    fn builds_device_topics_under_the_base_topic() {
+        let topics = topics();
+        assert_eq!(topics.area("abc", 1, "set"), "olarm/device/abc/area/1/set");
+        assert_eq!(topics.zone("abc", 12, "bypass/set"), "olarm/device/abc/zone/12/bypass/set");
+        assert_eq!(topics.device_availability("abc"), "olarm/device/abc/availability");
+        assert_eq!(topics.bridge("restart/set"), "olarm/bridge/restart/set");
+        assert_eq!(topics.discovery("sensor", "olarm_abc_1"), "homeassistant/sensor/olarm_abc_1/config");
+    }
+
+    #[test]
+    fn strip_base_splits_the_levels_after_the_base_topic() {
+        let topics = topics();
+        assert_eq!(
+            topics.strip_base("olarm/device/abc/area/1/set"),
+            Some(vec!["device", "abc", "area", "1", "set"])
+        );
+        assert_eq!(topics.strip_base("olarm"), None);
+        // Only whole levels match the base topic
+        assert_eq!(topics.strip_base("olarmx/device/abc"), None);
+        assert_eq!(topics.strip_base("other/device/abc"), None);
+    }
+
+    #[test]
+    fn account_namespaces_topics_and_device_keys() {
+        let topics = TopicBuilder::for_account(TopicsConfig::default(), "work");
+        assert_eq!(topics.device_key("abc"), "work_abc");
+        assert_eq!(topics.area("abc", 1, "set"), "olarm/work/device/abc/area/1/set");
+        assert_eq!(
+            topics.strip_base("olarm/work/device/abc/area/1/set"),
+            Some(vec!["device", "abc", "area", "1", "set"])
+        );
+        // Another account's topics aren't ours
+        assert_eq!(topics.strip_base("olarm/home/device/abc/area/1/set"), None);
+    }
+
+    #[test]
+    fn unique_ids_match_only_their_own_device() {
+        for unique_id in ["olarm_abc_area_1", "abc_3_binary", "olarm_abc_pgm_ob_1_state"] {
+            assert!(TopicBuilder::is_device_unique_id("abc", unique_id), "{}", unique_id);
+        }
+        // Keys that contain or extend "abc"
+        for unique_id in ["olarm_abc1_area_1", "abc1_3_binary", "olarm_xabc_area_1", "xabc_3_binary", "olarm_work_abc_area_1"] {
+            assert!(!TopicBuilder::is_device_unique_id("abc", unique_id), "{}", unique_id);
+        }
+        assert!(TopicBuilder::is_device_unique_id("work_abc", "olarm_work_abc_area_1"));
+        assert!(!TopicBuilder::is_device_unique_id("abc", "olarm_bridge_restart"));
+    }
+
+    #[test]
+    fn empty_account_leaves_topics_un_namespaced() {
+        let topics = TopicBuilder::for_account(TopicsConfig::default(), "");
+        assert_eq!(topics.device_key("abc"), "abc");
+        assert_eq!(topics.area("abc", 1, "set"), "olarm/device/abc/area/1/set");
+    }
+
+    #[test]
+    fn aliases_stand_in_for_device_ids() {
+        let aliases = HashMap::from([("abc".to_string(), "home".to_string())]);
+        let topics = TopicBuilder::new(TopicsConfig::default()).with_aliases(aliases);
+        assert_eq!(topics.area("abc", 1, "set"), "olarm/device/home/area/1/set");
+        assert_eq!(topics.device_id_of("home"), "abc");
+        // Unaliased levels are device ids already
+        assert_eq!(topics.device_id_of("xyz"), "xyz");
+        assert_eq!(topics.device_id_of("abc"), "abc");
+    }
+}